@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use colored::Colorize;
+
+use crate::error::{EnvcipherError, Result};
+
+#[cfg(unix)]
+use crate::keystore::agent;
+
+/// Starts the agent in the background, or in this process with `foreground`.
+#[cfg(unix)]
+pub fn start(ttl: Duration, foreground: bool) -> Result<()> {
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Instant;
+
+    let socket = agent::socket_path();
+
+    if foreground {
+        println!("envcipher agent listening on {}", socket.display());
+        return agent::serve(ttl);
+    }
+
+    if agent::status().is_some() {
+        println!("Agent already running at {}", socket.display());
+        return Ok(());
+    }
+
+    let mut command = Command::new(std::env::current_exe().map_err(EnvcipherError::Io)?);
+    command
+        .args(["agent", "start", "--foreground", "--ttl"])
+        .arg(humantime::format_duration(ttl).to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn().map_err(EnvcipherError::Io)?;
+
+    // Wait until it answers, so the next command already benefits.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while agent::status().is_none() {
+        if let Some(status) = child.try_wait().map_err(EnvcipherError::Io)? {
+            return Err(EnvcipherError::Agent(format!(
+                "exited during startup ({}); run `envcipher agent start --foreground` to see why",
+                status
+            )));
+        }
+        if Instant::now() >= deadline {
+            return Err(EnvcipherError::Agent("did not start in time".to_string()));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    println!("{}", "Agent started!".green().bold());
+    println!("PID:    {}", child.id());
+    println!("Socket: {}", socket.display());
+    println!(
+        "Keys are cached for {} after first use. Stop with {}.",
+        humantime::format_duration(ttl),
+        "envcipher agent stop".cyan()
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn stop() -> Result<()> {
+    if agent::stop() {
+        println!("{}", "Agent stopped. Cached keys were wiped.".green());
+    } else {
+        println!("No agent running.");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn status() -> Result<()> {
+    match agent::status() {
+        Some(status) => {
+            println!("Agent:  {}", "Running".green());
+            println!("Socket: {}", agent::socket_path().display());
+            println!("Keys:   {} cached", status.cached_keys);
+            println!("TTL:    {}", humantime::format_duration(status.ttl));
+        }
+        None => {
+            println!("Agent:  {}", "Not running".yellow());
+            println!("Start it with {}.", "envcipher agent start".cyan());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start(_ttl: Duration, _foreground: bool) -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+pub fn stop() -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+pub fn status() -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> EnvcipherError {
+    EnvcipherError::Agent("only supported on Unix".to_string())
+}
@@ -0,0 +1,313 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use sha2::{Digest, Sha256};
+
+use crate::cli::lock;
+use crate::cli::target::Target;
+use crate::crypto::secret::SecretKey;
+use crate::env::parser::{
+    ensure_state_dir, hash_directory_path, is_enciphered, read_env_file, state_file,
+};
+use crate::error::{EnvcipherError, Result};
+use crate::keystore;
+
+/// Hidden subcommand run by the detached watcher process.
+pub const WATCHER_COMMAND: &str = "__autolock";
+
+/// Watcher output goes here, next to the backups.
+pub const LOG_FILENAME: &str = "autolock.log";
+
+/// Digest of the plaintext as unlocked, handed to the watcher out of `ps` view.
+const DIGEST_ENV: &str = "ENVCIPHER_AUTOLOCK_DIGEST";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a new watcher waits for `spawn` to record its claim.
+const CLAIM_GRACE: Duration = Duration::from_secs(5);
+
+/// Longest wait between attempts while another envcipher process holds the file.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Starts a detached watcher that re-locks `env_path` once `after` has passed.
+/// Returns the watcher's pid.
+///
+/// The claim is recorded here rather than by the watcher, so a `lock` that runs
+/// before the watcher gets going still withdraws it.
+pub fn spawn(env_path: &Path, after: Duration) -> Result<u32> {
+    let log_path = log_path(env_path)?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(EnvcipherError::Io)?;
+
+    let mut command = Command::new(std::env::current_exe().map_err(EnvcipherError::Io)?);
+    command
+        .arg(WATCHER_COMMAND)
+        .arg("--after")
+        .arg(humantime::format_duration(after).to_string())
+        .arg(env_path)
+        .env(DIGEST_ENV, digest(&read_env_file(env_path)?))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().map_err(EnvcipherError::Io)?)
+        .stderr(log);
+
+    if let Some(project_dir) = env_path.parent() {
+        command.current_dir(project_dir);
+    }
+
+    // A process group of its own keeps Ctrl-C in the terminal from reaching the watcher.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let child = command.spawn().map_err(EnvcipherError::Io)?;
+
+    let claim = claim_path(env_path);
+    fs::create_dir_all(claim.parent().unwrap_or(Path::new("."))).map_err(EnvcipherError::Io)?;
+    fs::write(&claim, child.id().to_string()).map_err(EnvcipherError::Io)?;
+
+    Ok(child.id())
+}
+
+/// Withdraws any pending watcher for `env_path`; called whenever the file is locked.
+/// Also clears a recorded [`failure`], since the plaintext is gone.
+pub fn cancel(env_path: &Path) {
+    let _ = fs::remove_file(claim_path(env_path));
+    let _ = fs::remove_file(failure_path(env_path));
+}
+
+/// Why the last watcher gave up with the plaintext still on disk, if it did.
+pub fn failure(env_path: &Path) -> Option<String> {
+    fs::read_to_string(failure_path(env_path))
+        .ok()
+        .map(|reason| reason.trim().to_string())
+}
+
+pub fn log_path(env_path: &Path) -> Result<PathBuf> {
+    Ok(ensure_state_dir(env_path)?.join(LOG_FILENAME))
+}
+
+/// Body of the watcher process. Locks the file when the timer expires or the session
+/// ends (hang-up, termination, or the controlling terminal going away), unless it was
+/// locked in the meantime or a newer `unlock --for` took over.
+pub fn watch(env_path: &Path, after: Duration) -> Result<()> {
+    let claim = claim_path(env_path);
+    let pid = std::process::id().to_string();
+
+    // `spawn` writes the claim just after starting this process; give it a moment.
+    let started = Instant::now();
+    while !claim.exists() && started.elapsed() < CLAIM_GRACE {
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let unlocked = match std::env::var(DIGEST_ENV) {
+        Ok(digest) => digest,
+        Err(_) => digest(&read_env_file(env_path)?),
+    };
+    // By the time the session ends the keychain is often locked or gone, so the key is
+    // fetched now and held (locked in RAM) until it is needed.
+    let project_dir = env_path.parent().unwrap_or(Path::new("."));
+    let key = match keystore::retrieve_key_from_store(&hash_directory_path(project_dir)) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            log(
+                env_path,
+                &format!(
+                    "could not fetch the key yet ({}); trying again when locking",
+                    e
+                ),
+            );
+            None
+        }
+    };
+
+    let stop = stop_on_signals()?;
+    let had_terminal = terminal_attached();
+    // `Instant` stops while the machine sleeps; the promise is about wall time.
+    let deadline = boot_clock() + after;
+
+    log(
+        env_path,
+        &format!("re-locking in {}", humantime::format_duration(after)),
+    );
+
+    let reason = loop {
+        if !is_claimed(&claim, &pid) {
+            log(env_path, "locked or re-armed elsewhere; watcher exiting");
+            return Ok(());
+        }
+        if stop.load(Ordering::Relaxed) {
+            break "session ended";
+        }
+        if had_terminal && !terminal_attached() {
+            break "terminal closed";
+        }
+        if boot_clock() >= deadline {
+            break "timer expired";
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    match relock(env_path, &claim, &pid, &unlocked, key.as_ref()) {
+        Ok(true) => log(env_path, &format!("locked ({})", reason)),
+        Ok(false) => {}
+        Err(e) => {
+            // Only the log would know otherwise; `status` and `doctor` report this.
+            record_failure(env_path, &e);
+            log(env_path, &format!("could not lock ({}): {}", reason, e));
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Locks the file, retrying while another envcipher process holds it. Returns `false`
+/// when there turned out to be nothing for this watcher to do.
+///
+/// The claim stays until the file is locked, so giving up never goes unnoticed.
+fn relock(
+    env_path: &Path,
+    claim: &Path,
+    pid: &str,
+    unlocked: &str,
+    key: Option<&SecretKey>,
+) -> Result<bool> {
+    let target = Target::new(Some(env_path.to_path_buf()), None);
+    let mut retry_interval = POLL_INTERVAL;
+    let mut noted_change = false;
+
+    loop {
+        let current = read_env_file(env_path)?;
+        if is_enciphered(&current) {
+            cancel(env_path);
+            log(env_path, "already locked; leaving it alone");
+            return Ok(false);
+        }
+
+        if !noted_change && digest(&current) != unlocked {
+            noted_change = true;
+            log(
+                env_path,
+                "contents changed since unlock; locking the current version",
+            );
+        }
+
+        match lock::lock_target_with_key(&target, false, key) {
+            Ok(_) => return Ok(true),
+            Err(EnvcipherError::AlreadyEnciphered) => continue,
+            Err(EnvcipherError::FileBusy(_)) => {
+                log(
+                    env_path,
+                    &format!(
+                        "busy in another envcipher process; retrying in {}",
+                        humantime::format_duration(retry_interval)
+                    ),
+                );
+                thread::sleep(retry_interval);
+                retry_interval = (retry_interval * 2).min(MAX_RETRY_INTERVAL);
+
+                if claim.exists() && !is_claimed(claim, pid) {
+                    log(env_path, "re-armed elsewhere; watcher exiting");
+                    return Ok(false);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Time since boot, including time spent suspended.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn boot_clock() -> Duration {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid timespec for clock_gettime to fill in.
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut now) } != 0 {
+        return wall_clock();
+    }
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+/// Elsewhere the wall clock is the closest thing that keeps running during sleep.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn boot_clock() -> Duration {
+    wall_clock()
+}
+
+fn wall_clock() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn digest(contents: &str) -> String {
+    hex::encode(Sha256::digest(contents.as_bytes()))
+}
+
+/// The newest watcher for a file records its pid here; `lock` removes it.
+fn claim_path(env_path: &Path) -> PathBuf {
+    state_file(env_path, "autolock")
+}
+
+/// Written when a watcher gives up with the plaintext still on disk.
+fn failure_path(env_path: &Path) -> PathBuf {
+    state_file(env_path, "autolock-failed")
+}
+
+fn record_failure(env_path: &Path, error: &EnvcipherError) {
+    let path = failure_path(env_path);
+    let _ = fs::create_dir_all(path.parent().unwrap_or(Path::new(".")));
+    let _ = fs::write(&path, format!("{}\n", error));
+}
+
+fn is_claimed(claim: &Path, pid: &str) -> bool {
+    fs::read_to_string(claim).is_ok_and(|owner| owner.trim() == pid)
+}
+
+#[cfg(unix)]
+fn stop_on_signals() -> Result<Arc<AtomicBool>> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGHUP, SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&stop)).map_err(EnvcipherError::Io)?;
+    }
+    Ok(stop)
+}
+
+#[cfg(not(unix))]
+fn stop_on_signals() -> Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
+
+/// Whether the controlling terminal is still there; it disappears on logout.
+#[cfg(unix)]
+fn terminal_attached() -> bool {
+    OpenOptions::new().read(true).open("/dev/tty").is_ok()
+}
+
+#[cfg(not(unix))]
+fn terminal_attached() -> bool {
+    false
+}
+
+fn log(env_path: &Path, message: &str) {
+    eprintln!(
+        "[{}] {}: {}",
+        humantime::format_rfc3339_seconds(SystemTime::now()),
+        env_path.display(),
+        message
+    );
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::cli::run::load_env_vars;
+use crate::cli::target::Target;
+use crate::env::schema::{Schema, SchemaIssue};
+use crate::error::{EnvcipherError, Result};
+
+/// Validates the deciphered variables against `.env.schema` (or `.env.example`, or
+/// the given schema file).
+pub fn run(target: &Target, schema_path: Option<PathBuf>) -> Result<()> {
+    let env_path = target.env_file()?;
+    let schema = match schema_path {
+        // Relative paths follow -C, like --file.
+        Some(path) => Schema::load(&target.base_dir()?.join(path))?,
+        None => find_schema(&env_path)?,
+    };
+
+    let vars = load_env_vars(target)?;
+    let issues = schema.validate(&vars);
+
+    println!("{}", "envcipher check".bold());
+    println!("────────────────────────────────────────");
+    println!("Env file:    {}", env_path.display());
+    println!("Schema:      {}", schema.path.display());
+    println!();
+
+    for line in issue_lines(&issues) {
+        println!("{}", line);
+    }
+
+    let failed = issues.iter().filter(|issue| issue.is_error()).count();
+    if failed > 0 {
+        println!();
+        return Err(EnvcipherError::ChecksFailed(failed));
+    }
+
+    if issues.is_empty() {
+        println!("{}", "All variables match the schema.".green().bold());
+    }
+    Ok(())
+}
+
+/// Used by `run --check`: silent when the variables are valid, otherwise prints the
+/// problems and refuses.
+pub(crate) fn enforce(env_path: &Path, vars: &[(String, String)]) -> Result<()> {
+    let schema = find_schema(env_path)?;
+    let issues: Vec<SchemaIssue> = schema
+        .validate(vars)
+        .into_iter()
+        .filter(SchemaIssue::is_error)
+        .collect();
+
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {} does not match {}:",
+        "envcipher:".red().bold(),
+        env_path.display(),
+        schema.path.display()
+    );
+    for line in issue_lines(&issues) {
+        eprintln!("{}", line);
+    }
+    Err(EnvcipherError::ChecksFailed(issues.len()))
+}
+
+fn find_schema(env_path: &Path) -> Result<Schema> {
+    Schema::find(env_path)?.ok_or_else(|| {
+        EnvcipherError::SchemaNotFound(env_path.parent().unwrap_or(Path::new(".")).to_path_buf())
+    })
+}
+
+fn issue_lines(issues: &[SchemaIssue]) -> Vec<String> {
+    let width = issues
+        .iter()
+        .map(|issue| issue.key().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for issue in issues {
+        let (symbol, detail, description) = match issue {
+            SchemaIssue::Missing { description, .. } => (
+                "✗".red().bold(),
+                "missing".to_string(),
+                description.as_deref(),
+            ),
+            SchemaIssue::Malformed {
+                expected,
+                description,
+                ..
+            } => (
+                "✗".red().bold(),
+                format!("expected {}", expected),
+                description.as_deref(),
+            ),
+            SchemaIssue::Unexpected { .. } => {
+                ("!".yellow().bold(), "not in schema".to_string(), None)
+            }
+        };
+
+        lines.push(match description {
+            Some(description) => format!(
+                "{} {:<width$}  {} ({})",
+                symbol,
+                issue.key(),
+                detail,
+                description.dimmed()
+            ),
+            None => format!("{} {:<width$}  {}", symbol, issue.key(), detail),
+        });
+    }
+    lines
+}
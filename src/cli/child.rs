@@ -0,0 +1,126 @@
+//! Process plumbing for commands that keep running next to their child
+//! (`run --redact` and friends) instead of `exec`ing it.
+
+use std::process::ExitStatus;
+
+use crate::error::Result;
+
+/// Exit code to report for a child: its own code, or `128 + signal` when a signal
+/// killed it, as shells do.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Relays termination signals sent to envcipher on to the child, so supervisors that
+/// stop envcipher stop the child as well. Stops relaying when dropped.
+///
+/// Start it before spawning the child: from then on these signals no longer kill
+/// envcipher, and any that arrive before [`SignalForwarder::set_child`] are delivered
+/// to the child once it is known.
+#[cfg(unix)]
+pub struct SignalForwarder {
+    child: std::sync::Arc<std::sync::Mutex<ChildTarget>>,
+    handle: signal_hook::iterator::Handle,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+#[derive(Default)]
+struct ChildTarget {
+    pid: Option<i32>,
+    /// Signals received while there was no child to send them to.
+    pending: Vec<i32>,
+}
+
+#[cfg(unix)]
+impl SignalForwarder {
+    pub fn start() -> Result<Self> {
+        use std::sync::{Arc, Mutex};
+
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+        use signal_hook::iterator::SignalsInfo;
+        use signal_hook::iterator::exfiltrator::WithOrigin;
+        use signal_hook::low_level::siginfo::Cause;
+
+        use crate::error::EnvcipherError;
+
+        let child = Arc::new(Mutex::new(ChildTarget::default()));
+        let mut signals =
+            SignalsInfo::<WithOrigin>::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2])
+                .map_err(EnvcipherError::Io)?;
+        let handle = signals.handle();
+
+        let target = Arc::clone(&child);
+        let thread = std::thread::spawn(move || {
+            for origin in signals.forever() {
+                // Ctrl-C and friends already reached the child through the terminal's
+                // process group; sending them again would count twice.
+                if origin.cause == Cause::Kernel {
+                    continue;
+                }
+
+                let mut target = target.lock().unwrap_or_else(|e| e.into_inner());
+                match target.pid {
+                    Some(pid) => send(pid, origin.signal),
+                    None => target.pending.push(origin.signal),
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            handle,
+            thread: Some(thread),
+        })
+    }
+
+    /// Points the forwarder at the child, or a replacement (e.g. after a restart), and
+    /// delivers anything that arrived while there was none.
+    pub fn set_child(&self, child_pid: u32) {
+        let mut target = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        let pid = child_pid as i32;
+        target.pid = Some(pid);
+        for signal in target.pending.drain(..) {
+            send(pid, signal);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(pid: i32, signal: i32) {
+    // SAFETY: kill has no memory-safety preconditions.
+    unsafe {
+        libc::kill(pid, signal);
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Signals are not forwarded outside Unix; the console delivers Ctrl-C to the child.
+#[cfg(not(unix))]
+pub struct SignalForwarder;
+
+#[cfg(not(unix))]
+impl SignalForwarder {
+    pub fn start() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn set_child(&self, _child_pid: u32) {}
+}
@@ -0,0 +1,175 @@
+use std::path::Path;
+use std::process::Command;
+
+use colored::{Color, ColoredString, Colorize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::cli::target::Target;
+use crate::crypto::secret::{SecretKey, SecretString};
+use crate::env::backup::list_backups;
+use crate::env::diff::{VarChange, diff_env};
+use crate::env::parser::{
+    decipher_to_string, hash_directory_path, parse_env_content, read_env_file,
+};
+use crate::error::{EnvcipherError, Result};
+use crate::keystore;
+
+const MASK: &str = "****";
+
+/// Compares the current env file with a git revision (default `HEAD`), another file,
+/// or a backup. Both sides are deciphered with the project key.
+pub fn run(
+    target: &Target,
+    against: Option<String>,
+    backup: Option<usize>,
+    show_values: bool,
+) -> Result<()> {
+    let base_dir = target.base_dir()?;
+
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
+
+    let dir_hash = hash_directory_path(project_dir);
+    let key = keystore::retrieve_key_from_store(&dir_hash)?;
+
+    let (label, other_contents) = match (backup, against) {
+        (Some(number), _) => {
+            let backups = list_backups(&env_path)?;
+            let backup = number
+                .checked_sub(1)
+                .and_then(|index| backups.get(index))
+                .ok_or(EnvcipherError::BackupNotFound(number))?;
+            (
+                format!("backup #{}", number),
+                SecretString::from(read_env_file(&backup.path)?),
+            )
+        }
+        (None, Some(against)) if base_dir.join(&against).is_file() => {
+            let path = base_dir.join(&against);
+            (
+                path.display().to_string(),
+                SecretString::from(read_env_file(&path)?),
+            )
+        }
+        (None, against) => {
+            let rev = against.unwrap_or_else(|| "HEAD".to_string());
+            let contents = git_show(&env_path, &rev)?;
+            (format!("{}:{}", rev, file_name(&env_path)), contents)
+        }
+    };
+
+    let old = decipher_side(&key, &other_contents, &label)?;
+    let new = decipher_side(
+        &key,
+        &SecretString::from(read_env_file(&env_path)?),
+        &env_path.display().to_string(),
+    )?;
+
+    let changes = diff_env(&parse_env_content(&old), &parse_env_content(&new));
+
+    println!("{} {}", "---".red(), label);
+    println!("{} {}", "+++".green(), env_path.display());
+
+    if changes.is_empty() {
+        println!("No differences.");
+        return Ok(());
+    }
+
+    for change in &changes {
+        let (line, color) = match change {
+            VarChange::Added { key, value } => (
+                join(&["+ ", key, "=", shown(value, show_values)]),
+                Color::Green,
+            ),
+            VarChange::Removed { key, value } => (
+                join(&["- ", key, "=", shown(value, show_values)]),
+                Color::Red,
+            ),
+            VarChange::Changed { key, old, new } => (
+                join(&[
+                    "~ ",
+                    key,
+                    ": ",
+                    shown(old, show_values),
+                    " -> ",
+                    shown(new, show_values),
+                ]),
+                Color::Yellow,
+            ),
+        };
+        print_wiped(line, color);
+    }
+
+    if !show_values {
+        println!();
+        println!(
+            "Values are masked. Pass {} to reveal them.",
+            "--show-values".cyan()
+        );
+    }
+
+    Ok(())
+}
+
+fn decipher_side(key: &SecretKey, contents: &str, label: &str) -> Result<SecretString> {
+    decipher_to_string(key, contents).map_err(|e| match e {
+        EnvcipherError::Decipherment(reason) => {
+            EnvcipherError::Decipherment(format!("{}: {}", label, reason))
+        }
+        e => e,
+    })
+}
+
+fn git_show(env_path: &Path, rev: &str) -> Result<SecretString> {
+    let project_dir = env_path.parent().unwrap_or(Path::new("."));
+
+    // `./` makes the path relative to the working directory rather than the repo root.
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .arg("show")
+        .arg(format!("{}:./{}", rev, file_name(env_path)))
+        .output()
+        .map_err(|e| EnvcipherError::Io(std::io::Error::new(e.kind(), format!("git: {}", e))))?;
+
+    if !output.status.success() {
+        return Err(EnvcipherError::Io(std::io::Error::other(format!(
+            "git show {} failed: {}",
+            rev,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+
+    SecretString::from_utf8(Zeroizing::new(output.stdout)).ok_or_else(|| {
+        EnvcipherError::InvalidFormat(format!(
+            "{} at {} is not valid UTF-8",
+            file_name(env_path),
+            rev
+        ))
+    })
+}
+
+fn shown(value: &str, show_values: bool) -> &str {
+    if show_values { value } else { MASK }
+}
+
+/// Concatenates `parts` into a string allocated once, so no partial copies of the
+/// values are left behind by reallocation.
+fn join(parts: &[&str]) -> String {
+    let mut line = String::with_capacity(parts.iter().map(|part| part.len()).sum());
+    parts.iter().for_each(|part| line.push_str(part));
+    line
+}
+
+/// Prints `line` in `color` and wipes it, since it may hold deciphered values.
+fn print_wiped(line: String, color: Color) {
+    let mut styled = ColoredString::from(line).color(color);
+    println!("{}", styled);
+    styled.input.zeroize();
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
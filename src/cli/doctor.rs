@@ -0,0 +1,395 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use colored::Colorize;
+
+use crate::cli::autolock;
+use crate::cli::edit::{get_editor, is_program_available};
+use crate::cli::status::FileState;
+use crate::cli::target::{Marker, Target};
+use crate::crypto::aead::aes_decipher;
+use crate::crypto::secret::SecretString;
+use crate::env::parser::{
+    file_mode, hash_directory_path, is_readable_by_others, parse_enciphered_file, read_env_file,
+};
+use crate::error::{EnvcipherError, Result};
+use crate::keystore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Pass,
+    Info,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    level: Level,
+    label: &'static str,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn new(level: Level, label: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            level,
+            label,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    fn print(&self) {
+        let symbol = match self.level {
+            Level::Pass => "✓".green(),
+            Level::Info => "-".dimmed(),
+            Level::Warn => "!".yellow().bold(),
+            Level::Fail => "✗".red().bold(),
+        };
+
+        println!("{} {:<12} {}", symbol, self.label, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("  {} {}", "→".cyan(), fix);
+        }
+    }
+}
+
+pub fn run(target: &Target) -> Result<()> {
+    let base_dir = target.base_dir()?;
+
+    println!("{}", "envcipher doctor".bold());
+    println!("────────────────────────────────────────");
+    println!("Directory:   {}", base_dir.display());
+    println!();
+
+    let checks = collect_checks(target, &base_dir)?;
+    for check in &checks {
+        check.print();
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.level == Level::Fail)
+        .count();
+
+    println!();
+    if failed > 0 {
+        return Err(EnvcipherError::ChecksFailed(failed));
+    }
+
+    println!("{}", "No problems found.".green().bold());
+    Ok(())
+}
+
+fn collect_checks(target: &Target, base_dir: &Path) -> Result<Vec<Check>> {
+    let mut checks = Vec::new();
+
+    let marker_path = target.marker_path()?;
+    let marker = if marker_path.exists() {
+        match Marker::read(&marker_path) {
+            Ok(marker) => {
+                checks.push(Check::new(
+                    Level::Pass,
+                    "Marker",
+                    marker_path.display().to_string(),
+                ));
+                Some(marker)
+            }
+            Err(e) => {
+                checks.push(Check::new(Level::Warn, "Marker", e.to_string()).fix(
+                    "Restore .envcipher.json from version control or re-run `envcipher init`",
+                ));
+                Some(Marker::default())
+            }
+        }
+    } else {
+        checks.push(
+            Check::new(Level::Fail, "Marker", "no .envcipher.json found")
+                .fix("Run `envcipher init` in the project root"),
+        );
+        None
+    };
+
+    let env_path = match target.env_file() {
+        Ok(path) => {
+            checks.push(Check::new(
+                Level::Pass,
+                "Env file",
+                path.display().to_string(),
+            ));
+            path
+        }
+        Err(e) => {
+            checks.push(
+                Check::new(Level::Fail, "Env file", e.to_string())
+                    .fix("Create the file or point to it with `--file`"),
+            );
+            checks.push(editor_check());
+            return Ok(checks);
+        }
+    };
+
+    let project_dir = env_path.parent().unwrap_or(base_dir).to_path_buf();
+    let dir_hash = hash_directory_path(&project_dir);
+    let key_id = &dir_hash[..8];
+
+    if marker.is_some()
+        && let Some(marker_dir) = marker_path.parent()
+        && marker_dir != project_dir
+    {
+        checks.push(
+            Check::new(
+                Level::Warn,
+                "Location",
+                format!(
+                    "marker is in {} but the env file is in {}",
+                    marker_dir.display(),
+                    project_dir.display()
+                ),
+            )
+            .fix("Keys are tied to the env file's directory; keep .envcipher.json next to it"),
+        );
+    }
+
+    if let Some(Marker {
+        key_id: Some(stored_id),
+        ..
+    }) = &marker
+        && stored_id != key_id
+    {
+        checks.push(
+            Check::new(
+                Level::Warn,
+                "Key ID",
+                format!(
+                    "marker records {} but this path maps to {}",
+                    stored_id, key_id
+                ),
+            )
+            .fix("The project was moved or copied; run `envcipher import-key <KEY>` here"),
+        );
+    }
+
+    // The keychain is probed even when the agent has the key, since the agent only
+    // holds it until its TTL runs out.
+    let key_stored = match keystore::key_stored(&dir_hash) {
+        Ok(present) => {
+            checks.push(Check::new(
+                Level::Pass,
+                "Keychain",
+                "credential store is reachable",
+            ));
+            if present {
+                checks.push(Check::new(
+                    Level::Pass,
+                    "Key",
+                    format!("{} present", key_id),
+                ));
+            } else {
+                let fix = "Ask a teammate for `envcipher export-key` and run `envcipher import-key <KEY>`";
+                checks.push(
+                    Check::new(Level::Fail, "Key", format!("no key stored for {}", key_id))
+                        .fix(fix),
+                );
+            }
+            present
+        }
+        Err(e) => {
+            let fix = "Make sure the OS keychain is running and unlocked (Secret Service/KWallet on Linux)";
+            checks.push(Check::new(Level::Fail, "Keychain", e.to_string()).fix(fix));
+            false
+        }
+    };
+
+    let key_cached = keystore::key_cached(&dir_hash);
+    if key_cached {
+        checks.push(Check::new(
+            Level::Info,
+            "Agent",
+            format!("{} cached by the agent", key_id),
+        ));
+    }
+    let key_present = key_stored || key_cached;
+
+    match read_env_file(&env_path).map(SecretString::from) {
+        Ok(contents) => {
+            checks.push(contents_check(&contents, &dir_hash, key_present));
+            if let Some(check) = permissions_check(&env_path, FileState::of(&contents)) {
+                checks.push(check);
+            }
+        }
+        Err(e) => checks.push(
+            Check::new(Level::Fail, "Contents", e.to_string())
+                .fix("Check that the file is readable by your user"),
+        ),
+    }
+
+    if let Some(reason) = autolock::failure(&env_path) {
+        checks.push(
+            Check::new(
+                Level::Fail,
+                "Auto-lock",
+                format!("unlock --for could not re-lock: {}", reason),
+            )
+            .fix("Run `envcipher lock`; see .envcipher/autolock.log"),
+        );
+    }
+
+    checks.push(editor_check());
+    checks.extend(git_checks(&project_dir, &env_path));
+
+    Ok(checks)
+}
+
+fn contents_check(contents: &str, dir_hash: &str, key_present: bool) -> Check {
+    match FileState::of(contents) {
+        FileState::Unlocked => Check::new(Level::Warn, "Contents", "plaintext secrets on disk")
+            .fix("Run `envcipher lock` when you are done editing"),
+        FileState::Corrupted => Check::new(
+            Level::Fail,
+            "Contents",
+            "enciphered and plaintext content are mixed",
+        )
+        .fix("Run `envcipher unlock` to recover, then `envcipher lock`"),
+        FileState::Locked if !key_present => Check::new(
+            Level::Info,
+            "Contents",
+            "locked; cannot verify without the key",
+        ),
+        FileState::Locked => {
+            let decrypted = parse_enciphered_file(contents).and_then(|(nonce, ciphertext)| {
+                let key = keystore::retrieve_key_from_store(dir_hash)?;
+                aes_decipher(&key, &nonce, &ciphertext)
+            });
+
+            match decrypted {
+                Ok(_) => Check::new(
+                    Level::Pass,
+                    "Contents",
+                    "locked and decrypts with the stored key",
+                ),
+                Err(e) => Check::new(Level::Fail, "Contents", e.to_string())
+                    .fix("The stored key differs from the one used to lock; import the right key"),
+            }
+        }
+    }
+}
+
+fn permissions_check(env_path: &Path, state: FileState) -> Option<Check> {
+    let mode = file_mode(env_path)?;
+
+    if state != FileState::Locked && is_readable_by_others(mode) {
+        return Some(
+            Check::new(
+                Level::Warn,
+                "Permissions",
+                format!("plaintext file is readable by group/others ({:o})", mode),
+            )
+            .fix(format!("Run `chmod 600 {}`", env_path.display())),
+        );
+    }
+
+    Some(Check::new(
+        Level::Pass,
+        "Permissions",
+        format!("{:o}", mode),
+    ))
+}
+
+fn editor_check() -> Check {
+    let editor = get_editor();
+    let program = shell_words::split(&editor)
+        .ok()
+        .and_then(|args| args.into_iter().next())
+        .unwrap_or_default();
+
+    if !program.is_empty() && is_program_available(&program) {
+        Check::new(Level::Pass, "Editor", editor)
+    } else {
+        Check::new(Level::Warn, "Editor", format!("{} not found", editor))
+            .fix("Set $EDITOR to an installed editor for `envcipher edit`")
+    }
+}
+
+fn git_checks(project_dir: &Path, env_path: &Path) -> Vec<Check> {
+    // Asking git resolves `core.hooksPath`, worktrees and submodules.
+    let Some(hook_path) = git_output(
+        project_dir,
+        &["rev-parse", "--git-path", "hooks/pre-commit"],
+    ) else {
+        return vec![Check::new(Level::Info, "Git", "not a git repository")];
+    };
+    let hook_path = project_dir.join(hook_path);
+
+    let mut checks = Vec::new();
+
+    let hook_installed = fs::read_to_string(&hook_path)
+        .map(|hook| hook.contains("envcipher"))
+        .unwrap_or(false);
+
+    if hook_installed {
+        checks.push(Check::new(
+            Level::Pass,
+            "Git hook",
+            "pre-commit hook runs envcipher",
+        ));
+    } else {
+        checks.push(
+            Check::new(Level::Info, "Git hook", "no envcipher pre-commit hook").fix(format!(
+                "Add `envcipher status >/dev/null` to {} to block plaintext commits",
+                hook_path.display()
+            )),
+        );
+    }
+
+    // `path: filter: <value>`, from whichever .gitattributes applies to the env file.
+    let env_file = env_path.strip_prefix(project_dir).unwrap_or(env_path);
+    let attributes_use_filter = git_output(
+        project_dir,
+        &["check-attr", "filter", "--", &env_file.to_string_lossy()],
+    )
+    .is_some_and(|attributes| attributes.ends_with(": envcipher"));
+
+    if attributes_use_filter {
+        let configured =
+            git_output(project_dir, &["config", "--get", "filter.envcipher.clean"]).is_some();
+
+        if configured {
+            checks.push(Check::new(
+                Level::Pass,
+                "Git filter",
+                "envcipher filter configured",
+            ));
+        } else {
+            checks.push(
+                Check::new(
+                    Level::Fail,
+                    "Git filter",
+                    ".gitattributes uses filter=envcipher but git has no such filter",
+                )
+                .fix("Configure `filter.envcipher.clean`/`smudge` in your git config"),
+            );
+        }
+    }
+
+    checks
+}
+
+/// Trimmed stdout of a successful git command run in `dir`.
+fn git_output(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
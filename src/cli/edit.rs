@@ -1,51 +1,131 @@
 use std::env;
 use std::fs;
-use std::process::Command;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 use colored::Colorize;
-use tempfile::NamedTempFile;
+use tempfile::TempDir;
+use zeroize::Zeroizing;
 
-use crate::crypto::aead::{aes_decipher, aes_encipher};
+use crate::cli::lint::issue_lines;
+use crate::cli::prompt;
+use crate::cli::target::Target;
+use crate::crypto::secret::SecretString;
+use crate::env::backup::save_backup;
+use crate::env::filelock::EnvFileLock;
+use crate::env::lint::{LintIssue, lint_env};
+use crate::env::merge::merge_env;
 use crate::env::parser::{
-    find_env_file, format_enciphered_text, hash_directory_path, is_enciphered,
-    parse_enciphered_file, read_env_file, write_to_env_file,
+    decipher_to_string, encipher_and_verify, hash_directory_path, read_env_file, write_to_env_file,
 };
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run() -> Result<()> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+pub fn run(target: &Target) -> Result<()> {
+    let base_dir = target.base_dir()?;
 
-    let env_path = find_env_file(&current_dir)?;
-    let project_dir = env_path.parent().unwrap_or(&current_dir);
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
 
-    let dir_hash = hash_directory_path(project_dir);
-    let key =
-        keystore::retrieve_key_from_store(&dir_hash).map_err(|_| EnvcipherError::NotInitialized)?;
+    let _lock = EnvFileLock::acquire(&env_path)?;
 
-    let contents = read_env_file(&env_path)?;
-    let mut initial_plaintext = String::new();
+    let dir_hash = hash_directory_path(project_dir);
+    let key = keystore::retrieve_key_from_store(&dir_hash)?;
 
-    if is_enciphered(&contents) {
-        let (nonce, ciphertext) = parse_enciphered_file(&contents)?;
-        let plaintext_bytes = aes_decipher(&key, &nonce, &ciphertext)?;
-        initial_plaintext = String::from_utf8(plaintext_bytes).map_err(|_| {
-            EnvcipherError::Decipherment("deciphered content is not valid UTF-8".to_string())
-        })?;
-    } else if !contents.is_empty() {
-        initial_plaintext = contents;
-    }
+    let contents = SecretString::from(read_env_file(&env_path)?);
+    let initial_plaintext = decipher_to_string(&key, &contents)?;
 
-    // NamedTempFile created with 0600 permissions by default on unix.
-    let temp_file = NamedTempFile::new().map_err(EnvcipherError::Io)?;
-    fs::write(temp_file.path(), &initial_plaintext).map_err(EnvcipherError::Io)?;
+    let file_name = env_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(".env");
+    let scratch = ScratchFile::create(file_name, &initial_plaintext)?;
 
     let editor = get_editor();
 
     println!("Opening enciphered .env in {}...", editor);
 
+    let new_plaintext = loop {
+        open_editor(&editor, scratch.path())?;
+
+        let bytes = Zeroizing::new(fs::read(scratch.path()).map_err(EnvcipherError::Io)?);
+        if bytes.as_slice() == initial_plaintext.as_bytes() {
+            break initial_plaintext.clone();
+        }
+
+        match validate(bytes)? {
+            Validated::Valid(plaintext) => break plaintext,
+            Validated::Invalid(plaintext) => {
+                let choice = prompt::choose(
+                    "Re-edit, save anyway, or discard your changes",
+                    &[('e', "edit"), ('s', "save"), ('d', "discard")],
+                )?;
+                match choice {
+                    'e' => continue,
+                    's' => break plaintext,
+                    _ => {
+                        println!("{}", "Discarded. No changes written.".yellow());
+                        return Ok(());
+                    }
+                }
+            }
+            Validated::NotUtf8 => {
+                let choice = prompt::choose(
+                    "Re-edit or discard your changes",
+                    &[('e', "edit"), ('d', "discard")],
+                )?;
+                if choice == 'e' {
+                    continue;
+                }
+                println!("{}", "Discarded. No changes written.".yellow());
+                return Ok(());
+            }
+        }
+    };
+
+    // Verify it didn't change if the user just quit without saving.
+    if new_plaintext == initial_plaintext {
+        println!("{}", "No changes made.".yellow());
+        return Ok(());
+    }
+
+    // The lock only keeps out other envcipher processes; git checkouts or copies can still
+    // replace the file while the editor is open.
+    let current_contents = SecretString::from(read_env_file(&env_path)?);
+    let final_plaintext = if *current_contents == *contents {
+        new_plaintext
+    } else {
+        println!(
+            "{} {} changed on disk while you were editing.",
+            "Warning:".yellow(),
+            env_path.display()
+        );
+
+        let current_plaintext = decipher_to_string(&key, &current_contents)?;
+        match resolve_conflict(&initial_plaintext, new_plaintext, &current_plaintext)? {
+            Some(plaintext) => plaintext,
+            None => {
+                println!("{}", "Aborted. No changes written.".yellow());
+                return Ok(());
+            }
+        }
+    };
+
+    let enciphered_content = encipher_and_verify(&key, &final_plaintext)?;
+
+    save_backup(&env_path, &key, &current_contents)?;
+    write_to_env_file(&env_path, &enciphered_content)?;
+
+    // The scratch file is wiped and deleted when `scratch` goes out of scope here.
+
+    println!("{}", "Changes saved and enciphered!".green().bold());
+    Ok(())
+}
+
+fn open_editor(editor: &str, path: &Path) -> Result<()> {
     // Use shell-words to fallback splits (e.g. "code --wait").
-    let args = shell_words::split(&editor).map_err(|e| {
+    let args = shell_words::split(editor).map_err(|e| {
         EnvcipherError::EditorFailed(format!("Failed to parse EDITOR command: {}", e))
     })?;
 
@@ -57,7 +137,8 @@ pub fn run() -> Result<()> {
 
     let status = Command::new(&args[0])
         .args(&args[1..])
-        .arg(temp_file.path())
+        .args(hardening_args(&args[0]))
+        .arg(path)
         .status()
         .map_err(|e| EnvcipherError::EditorFailed(format!("failed to launch {}: {}", editor, e)))?;
 
@@ -67,27 +148,223 @@ pub fn run() -> Result<()> {
             editor, status
         )));
     }
+    Ok(())
+}
 
-    let new_plaintext = fs::read_to_string(temp_file.path()).map_err(EnvcipherError::Io)?;
+/// Flags that stop editors known to leave copies of the buffer behind (swap, undo,
+/// backup and history files) from doing so.
+pub fn hardening_args(program: &str) -> Vec<&'static str> {
+    let name = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
 
-    // Verify it didn't change if the user just quit without saving.
-    if new_plaintext == initial_plaintext {
-        println!("{}", "No changes made.".yellow());
-        return Ok(());
+    let vim = match name {
+        // Usually vim, but `get_editor` falls back to `vi` exactly when there is no
+        // `vim` on the PATH, so it may be busybox vi or nvi, which reject vim's flags.
+        "vi" | "view" => is_vim(program),
+        _ => is_vim_name(name),
+    };
+
+    if vim {
+        return vec![
+            "-n",
+            "-i",
+            "NONE",
+            "--cmd",
+            "set noundofile nobackup nowritebackup",
+        ];
     }
 
-    let (ciphertext, nonce) = aes_encipher(&key, new_plaintext.as_bytes())?;
-    let enciphered_content = format_enciphered_text(&nonce, &ciphertext);
+    match name {
+        // Restricted mode also turns off backups and the search/position history.
+        "nano" => vec!["--restricted"],
+        _ => Vec::new(),
+    }
+}
 
-    write_to_env_file(&env_path, &enciphered_content)?;
+/// Debian installs the real binary as `vim.basic`, `vim.tiny` etc.
+fn is_vim_name(name: &str) -> bool {
+    matches!(name, "vim" | "nvim" | "gvim" | "mvim") || name.starts_with("vim.")
+}
 
-    // Temp file is automatically deleted when `temp_file` goes out of scope here.
+/// Follows symlinks (e.g. through `/etc/alternatives`) to the real binary; one that is
+/// not a symlink to anything else is asked for its version instead.
+fn is_vim(program: &str) -> bool {
+    let Some(path) = find_program(program) else {
+        return false;
+    };
+    let resolved = fs::canonicalize(&path).unwrap_or(path);
 
-    println!("{}", "Changes saved and enciphered!".green().bold());
-    Ok(())
+    match resolved.file_name().and_then(|name| name.to_str()) {
+        Some(name) if is_vim_name(name) => true,
+        Some("vi" | "view") => Command::new(&resolved)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| {
+                output.stdout.starts_with(b"VIM") || output.stdout.starts_with(b"NVIM")
+            }),
+        _ => false,
+    }
+}
+
+/// `program` itself if it is a path, otherwise the first match on the PATH.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The plaintext handed to the editor, in a private (0700) directory on a RAM-backed
+/// filesystem where one exists. Named like the env file so editors highlight it.
+///
+/// A memfd would keep it off any filesystem, but editors that save by writing a new
+/// file and renaming it over the old one need a real directory.
+struct ScratchFile {
+    dir: TempDir,
+    path: PathBuf,
+}
+
+impl ScratchFile {
+    fn create(file_name: &str, contents: &str) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("envcipher-edit-")
+            .tempdir_in(scratch_base_dir())
+            .map_err(EnvcipherError::Io)?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o700))
+                .map_err(EnvcipherError::Io)?;
+            options.mode(0o600);
+        }
+
+        let path = dir.path().join(file_name);
+        let mut file = options.open(&path).map_err(EnvcipherError::Io)?;
+        file.write_all(contents.as_bytes())
+            .map_err(EnvcipherError::Io)?;
+
+        Ok(Self { dir, path })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchFile {
+    /// Overwrites everything the editor left in the directory before it is removed,
+    /// so the plaintext does not linger in freed blocks.
+    fn drop(&mut self) {
+        let Ok(entries) = fs::read_dir(self.dir.path()) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            if let Ok(mut file) = fs::OpenOptions::new().write(true).open(&path) {
+                let _ = file.write_all(&vec![0; metadata.len() as usize]);
+                let _ = file.sync_all();
+            }
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// `/dev/shm`, then `$XDG_RUNTIME_DIR`, then the system temp dir.
+fn scratch_base_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if cfg!(target_os = "linux") && shm.is_dir() {
+        return shm.to_path_buf();
+    }
+
+    dirs::runtime_dir().unwrap_or_else(env::temp_dir)
+}
+
+enum Validated {
+    Valid(SecretString),
+    /// Parses, but not the way it was probably meant to; already reported.
+    Invalid(SecretString),
+    /// Already reported; cannot be saved.
+    NotUtf8,
+}
+
+/// Checks the edited file before it is enciphered, printing line-numbered problems
+/// the way `crontab -e` and `visudo` do.
+fn validate(bytes: Zeroizing<Vec<u8>>) -> Result<Validated> {
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        let valid = &bytes[..e.valid_up_to()];
+        let line = valid.iter().filter(|&&byte| byte == b'\n').count() + 1;
+        println!(
+            "{} the edited file is not valid UTF-8 (line {}).",
+            "Error:".red().bold(),
+            line
+        );
+        return Ok(Validated::NotUtf8);
+    }
+    let Some(plaintext) = SecretString::from_utf8(bytes) else {
+        return Ok(Validated::NotUtf8);
+    };
+
+    let errors: Vec<LintIssue> = lint_env(&plaintext)
+        .into_iter()
+        .filter(LintIssue::is_error)
+        .collect();
+    if errors.is_empty() {
+        return Ok(Validated::Valid(plaintext));
+    }
+
+    println!("{} the edited file has problems:", "Error:".red().bold());
+    for line in issue_lines(&errors) {
+        println!("{}", line);
+    }
+    Ok(Validated::Invalid(plaintext))
+}
+
+/// Returns the plaintext to save, or `None` when the user aborts.
+fn resolve_conflict(base: &str, ours: SecretString, theirs: &str) -> Result<Option<SecretString>> {
+    let choice = prompt::choose(
+        "Merge your changes into it, overwrite it, or abort",
+        &[('m', "merge"), ('o', "overwrite"), ('a', "abort")],
+    )?;
+
+    match choice {
+        'm' => match merge_env(base, &ours, theirs) {
+            Ok(merged) => Ok(Some(merged)),
+            Err(conflicts) => {
+                println!(
+                    "{} both sides changed {}",
+                    "Conflict:".red().bold(),
+                    conflicts.join(", ")
+                );
+                let choice =
+                    prompt::choose("Overwrite or abort", &[('o', "overwrite"), ('a', "abort")])?;
+                Ok((choice == 'o').then_some(ours))
+            }
+        },
+        'o' => Ok(Some(ours)),
+        _ => Ok(None),
+    }
 }
 
-fn get_editor() -> String {
+pub(crate) fn get_editor() -> String {
     if let Ok(editor) = env::var("EDITOR")
         && !editor.is_empty()
     {
@@ -111,7 +388,7 @@ fn get_editor() -> String {
     "vi".to_string()
 }
 
-fn is_program_available(program: &str) -> bool {
+pub(crate) fn is_program_available(program: &str) -> bool {
     Command::new("which")
         .arg(program)
         .output()
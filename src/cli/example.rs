@@ -0,0 +1,47 @@
+use colored::Colorize;
+
+use crate::cli::run::load_plaintext;
+use crate::cli::target::Target;
+use crate::env::example::render_example;
+use crate::env::parser::{parse_env_content, read_env_file, write_shared_file};
+use crate::env::schema::EXAMPLE_FILENAME;
+use crate::error::{EnvcipherError, Result};
+
+/// Writes `.env.example` next to the env file, or with `check` only verifies that it
+/// is up to date (for pre-commit hooks and CI).
+pub fn run(target: &Target, check: bool) -> Result<()> {
+    let env_path = target.env_file()?;
+    let example_path = env_path.with_file_name(EXAMPLE_FILENAME);
+
+    let plaintext = load_plaintext(target)?;
+    let existing = example_path
+        .exists()
+        .then(|| read_env_file(&example_path))
+        .transpose()?;
+    let rendered = render_example(&plaintext, existing.as_deref());
+
+    if check {
+        if existing.as_deref() != Some(rendered.as_str()) {
+            return Err(EnvcipherError::ExampleOutdated(example_path));
+        }
+
+        println!("{} is up to date.", example_path.display());
+        return Ok(());
+    }
+
+    if existing.as_deref() == Some(rendered.as_str()) {
+        println!("{} is already up to date.", example_path.display());
+        return Ok(());
+    }
+
+    write_shared_file(&example_path, &rendered)?;
+
+    println!("{}", "Example written!".green().bold());
+    println!("File: {}", example_path.display());
+    println!(
+        "{} variables, values replaced by placeholders. Commit it so others know what to set.",
+        parse_env_content(&rendered).len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use colored::Colorize;
+
+use crate::cli::autolock;
+use crate::cli::target::Target;
+use crate::crypto::secret::SecretString;
+use crate::env::backup::{list_backups, save_backup};
+use crate::env::diff::{VarChange, diff_env};
+use crate::env::filelock::EnvFileLock;
+use crate::env::parser::{
+    decipher_to_string, hash_directory_path, parse_env_content, read_env_file, write_to_env_file,
+};
+use crate::error::{EnvcipherError, Result};
+use crate::keystore;
+
+pub fn list(target: &Target) -> Result<()> {
+    let base_dir = target.base_dir()?;
+
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
+
+    let backups = list_backups(&env_path)?;
+    if backups.is_empty() {
+        println!("No backups for {}", env_path.display());
+        return Ok(());
+    }
+
+    let dir_hash = hash_directory_path(project_dir);
+    let key = keystore::retrieve_key_from_store(&dir_hash)?;
+
+    println!("{}", "envcipher history".bold());
+    println!("────────────────────────────────────────");
+    println!("File: {}", env_path.display());
+    println!();
+    println!(
+        "{:>3}  {:<20}  {:>4}  {}",
+        "#".bold(),
+        "SAVED".bold(),
+        "KEYS".bold(),
+        "CHANGED AFTERWARDS".bold()
+    );
+
+    // Each backup is compared with the version that replaced it.
+    let current = decipher_to_string(&key, &SecretString::from(read_env_file(&env_path)?))?;
+    let mut newer = parse_env_content(&current);
+
+    for (index, backup) in backups.iter().enumerate() {
+        let saved = humantime::format_rfc3339_seconds(backup.created).to_string();
+
+        let plaintext = match read_env_file(&backup.path)
+            .and_then(|contents| decipher_to_string(&key, &contents))
+        {
+            Ok(plaintext) => plaintext,
+            Err(e) => {
+                println!(
+                    "{:>3}  {:<20}  {:>4}  {}",
+                    index + 1,
+                    saved,
+                    "?",
+                    e.to_string().red()
+                );
+                continue;
+            }
+        };
+
+        let vars = parse_env_content(&plaintext);
+        let key_count = vars
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<HashSet<_>>()
+            .len();
+
+        println!(
+            "{:>3}  {:<20}  {:>4}  {}",
+            index + 1,
+            saved,
+            key_count,
+            summarize(&diff_env(&vars, &newer))
+        );
+
+        newer = vars;
+    }
+
+    println!();
+    println!("Run {} to roll back.", "envcipher restore <#>".cyan());
+
+    Ok(())
+}
+
+pub fn restore(target: &Target, number: usize) -> Result<()> {
+    let base_dir = target.base_dir()?;
+
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
+
+    let _lock = EnvFileLock::acquire(&env_path)?;
+
+    let backups = list_backups(&env_path)?;
+    let backup = number
+        .checked_sub(1)
+        .and_then(|index| backups.get(index))
+        .ok_or(EnvcipherError::BackupNotFound(number))?;
+
+    let dir_hash = hash_directory_path(project_dir);
+    let key = keystore::retrieve_key_from_store(&dir_hash)?;
+
+    // Refuse backups the current key cannot read before touching the env file.
+    let restored = read_env_file(&backup.path)?;
+    decipher_to_string(&key, &restored)?;
+
+    // Keep the current version so the restore itself can be undone.
+    let current = SecretString::from(read_env_file(&env_path)?);
+    let saved = save_backup(&env_path, &key, &current)?;
+    write_to_env_file(&env_path, &restored)?;
+    autolock::cancel(&env_path);
+
+    println!("{}", "Restored!".green().bold());
+    println!("File: {}", env_path.display());
+    println!(
+        "Backup from {} is now the enciphered contents.",
+        humantime::format_rfc3339_seconds(backup.created)
+    );
+    if saved {
+        println!("The previous contents were saved as backup #1.");
+    }
+
+    Ok(())
+}
+
+fn summarize(changes: &[VarChange]) -> String {
+    if changes.is_empty() {
+        return "-".dimmed().to_string();
+    }
+
+    changes
+        .iter()
+        .map(|change| match change {
+            VarChange::Added { key, .. } => format!("+{}", key).green().to_string(),
+            VarChange::Removed { key, .. } => format!("-{}", key).red().to_string(),
+            VarChange::Changed { key, .. } => format!("~{}", key).yellow().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
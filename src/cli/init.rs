@@ -1,34 +1,29 @@
 use colored::Colorize;
-use std::env;
 use std::fs;
 
+use crate::cli::target::Target;
 use crate::crypto::aead::generate_key;
-use crate::env::parser::{find_env_file, hash_directory_path};
+use crate::env::parser::{hash_directory_path, write_plaintext_env_file};
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run() -> Result<()> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+pub fn run(target: &Target) -> Result<()> {
+    let base_dir = target.base_dir()?;
 
-    let marker_path = current_dir.join(".envcipher.json");
+    let marker_path = target.marker_path()?;
     if marker_path.exists() {
         return Err(EnvcipherError::AlreadyInitialized);
     }
 
-    let env_path = match find_env_file(&current_dir) {
-        Ok(path) => {
-            println!("Found .env at: {}", path.display());
-            path
-        }
-        Err(_) => {
-            let new_env = current_dir.join(".env");
-            fs::write(&new_env, "# Environment variables\n").map_err(EnvcipherError::Io)?;
-            println!("Created new .env file");
-            new_env
-        }
-    };
+    let env_path = target.env_file_path()?;
+    if env_path.exists() {
+        println!("Found .env at: {}", env_path.display());
+    } else {
+        write_plaintext_env_file(&env_path, "# Environment variables\n")?;
+        println!("Created new env file at: {}", env_path.display());
+    }
 
-    let project_dir = env_path.parent().unwrap_or(&current_dir).to_path_buf();
+    let project_dir = env_path.parent().unwrap_or(&base_dir).to_path_buf();
     let dir_hash = hash_directory_path(&project_dir);
 
     // Key might exist but marker doesn't (possibly from failed previous init).
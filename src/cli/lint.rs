@@ -0,0 +1,121 @@
+use colored::Colorize;
+
+use crate::cli::target::Target;
+use crate::crypto::secret::SecretString;
+use crate::env::backup::save_backup;
+use crate::env::filelock::EnvFileLock;
+use crate::env::lint::{LintIssue, fix_env, lint_env};
+use crate::env::parser::{
+    decipher_to_string, encipher_and_verify, hash_directory_path, is_enciphered, read_env_file,
+    write_plaintext_env_file, write_to_env_file,
+};
+use crate::error::{EnvcipherError, Result};
+use crate::keystore;
+
+/// Reports formatting problems in the deciphered env file. With `fix`, rewrites it
+/// with the fixable ones resolved, re-enciphering it if it was locked.
+pub fn run(target: &Target, fix: bool) -> Result<()> {
+    let base_dir = target.base_dir()?;
+
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
+
+    let _lock = EnvFileLock::acquire(&env_path)?;
+
+    // Not enciphered when the file is unlocked.
+    let contents = SecretString::from(read_env_file(&env_path)?);
+    let locked = is_enciphered(&contents);
+    let key = if locked {
+        Some(keystore::retrieve_key_from_store(&hash_directory_path(
+            project_dir,
+        ))?)
+    } else {
+        None
+    };
+    let plaintext = match &key {
+        Some(key) => decipher_to_string(key, &contents)?,
+        None => contents.clone(),
+    };
+
+    println!("{}", "envcipher lint".bold());
+    println!("────────────────────────────────────────");
+    println!("Env file:    {}", env_path.display());
+    println!();
+
+    let mut issues = lint_env(&plaintext);
+
+    if fix {
+        let fixed = fix_env(&plaintext);
+        if fixed != plaintext {
+            match &key {
+                Some(key) => {
+                    let enciphered_content = encipher_and_verify(key, &fixed)?;
+                    save_backup(&env_path, key, &contents)?;
+                    write_to_env_file(&env_path, &enciphered_content)?;
+                }
+                None => write_plaintext_env_file(&env_path, &fixed)?,
+            }
+
+            let remaining = lint_env(&fixed);
+            println!(
+                "{} {} problem(s).",
+                "Fixed".green().bold(),
+                issues.len().saturating_sub(remaining.len())
+            );
+            if !remaining.is_empty() {
+                println!();
+            }
+            issues = remaining;
+        }
+    }
+
+    for line in issue_lines(&issues) {
+        println!("{}", line);
+    }
+
+    if issues.is_empty() {
+        println!("{}", "No problems found.".green().bold());
+        return Ok(());
+    }
+
+    let fixable = issues.iter().filter(|issue| issue.is_fixable()).count();
+    if fixable > 0 {
+        println!();
+        println!(
+            "{}",
+            format!("{} can be fixed with `envcipher lint --fix`.", fixable).dimmed()
+        );
+    }
+
+    let failed = issues.iter().filter(|issue| issue.is_error()).count();
+    if failed > 0 {
+        println!();
+        return Err(EnvcipherError::ChecksFailed(failed));
+    }
+    Ok(())
+}
+
+pub(crate) fn issue_lines(issues: &[LintIssue]) -> Vec<String> {
+    let width = issues
+        .iter()
+        .map(|issue| issue.line.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    issues
+        .iter()
+        .map(|issue| {
+            let symbol = if issue.is_error() {
+                "✗".red().bold()
+            } else {
+                "!".yellow().bold()
+            };
+            format!(
+                "{} line {:<width$}  {}",
+                symbol,
+                issue.line,
+                issue.message()
+            )
+        })
+        .collect()
+}
@@ -1,22 +1,62 @@
-use std::env;
+use std::path::PathBuf;
 
 use colored::Colorize;
 
-use crate::crypto::aead::aes_encipher;
+use crate::cli::autolock;
+use crate::cli::target::Target;
+use crate::cli::workspace;
+use crate::crypto::secret::{SecretKey, SecretString};
+use crate::env::backup::{save_backup, take_unlocked_ciphertext};
+use crate::env::filelock::EnvFileLock;
 use crate::env::parser::{
-    find_env_file, format_enciphered_text, has_corrupted_format, hash_directory_path,
-    is_enciphered, read_env_file, write_to_env_file,
+    decipher_to_string, encipher_and_verify, has_corrupted_format, hash_directory_path,
+    is_enciphered, read_env_file, set_file_mode, take_locked_mode, write_to_env_file,
 };
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run() -> Result<()> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+pub fn run(target: &Target, all: bool, keep_mode: bool) -> Result<()> {
+    if all {
+        return workspace::for_each_project(target, |project| {
+            match lock_target(project, keep_mode) {
+                Ok(_) => Ok("locked".to_string()),
+                Err(EnvcipherError::AlreadyEnciphered) => Ok("already locked".to_string()),
+                Err(e) => Err(e),
+            }
+        });
+    }
+
+    let env_path = lock_target(target, keep_mode)?;
+
+    println!("{}", "Locked!".green().bold());
+    println!("File: {}", env_path.display());
+    println!();
+    println!(
+        "Your .env is now enciphered. Run {} to decipher.",
+        "envcipher unlock".cyan()
+    );
+
+    Ok(())
+}
+
+pub(crate) fn lock_target(target: &Target, keep_mode: bool) -> Result<PathBuf> {
+    lock_target_with_key(target, keep_mode, None)
+}
+
+/// Like [`lock_target`], but with `key` (when given) instead of one fetched now.
+pub(crate) fn lock_target_with_key(
+    target: &Target,
+    keep_mode: bool,
+    key: Option<&SecretKey>,
+) -> Result<PathBuf> {
+    let base_dir = target.base_dir()?;
 
-    let env_path = find_env_file(&current_dir)?;
-    let project_dir = env_path.parent().unwrap_or(&current_dir);
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
 
-    let contents = read_env_file(&env_path)?;
+    let _lock = EnvFileLock::acquire(&env_path)?;
+
+    let contents = SecretString::from(read_env_file(&env_path)?);
 
     if is_enciphered(&contents) {
         return Err(EnvcipherError::AlreadyEnciphered);
@@ -34,22 +74,31 @@ pub fn run() -> Result<()> {
         println!();
     }
 
-    let dir_hash = hash_directory_path(project_dir);
-    let key =
-        keystore::retrieve_key_from_store(&dir_hash).map_err(|_| EnvcipherError::NotInitialized)?;
-
-    let (ciphertext, nonce) = aes_encipher(&key, contents.as_bytes())?;
+    let fetched;
+    let key = match key {
+        Some(key) => key,
+        None => {
+            fetched = keystore::retrieve_key_from_store(&hash_directory_path(project_dir))?;
+            &fetched
+        }
+    };
 
-    let enciphered_content = format_enciphered_text(&nonce, &ciphertext);
+    let enciphered_content = encipher_and_verify(key, &contents)?;
+    // Keep the version `unlock` replaced, unless nothing changed since.
+    if let Some(previous) = take_unlocked_ciphertext(&env_path)
+        && decipher_to_string(key, &previous).map_or(true, |plaintext| *plaintext != *contents)
+    {
+        save_backup(&env_path, key, &previous)?;
+    }
     write_to_env_file(&env_path, &enciphered_content)?;
+    autolock::cancel(&env_path);
 
-    println!("{}", "Locked!".green().bold());
-    println!("File: {}", env_path.display());
-    println!();
-    println!(
-        "Your .env is now enciphered. Run {} to decipher.",
-        "envcipher unlock".cyan()
-    );
+    // Ciphertext keeps the private mode unless asked to restore the pre-unlock one.
+    if let Some(mode) = take_locked_mode(&env_path)
+        && keep_mode
+    {
+        set_file_mode(&env_path, mode)?;
+    }
 
-    Ok(())
+    Ok(env_path)
 }
@@ -1,18 +1,45 @@
+pub mod agent;
+pub mod autolock;
+pub mod check;
+pub mod child;
+pub mod diff;
+pub mod doctor;
 pub mod edit;
+pub mod example;
+pub mod history;
 pub mod init;
 pub mod key;
+pub mod lint;
 pub mod lock;
+pub mod prompt;
 pub mod run;
+pub mod shell;
 pub mod status;
+pub mod target;
 pub mod unlock;
+pub mod workspace;
 
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::crypto::memory::MemoryGuard;
+use crate::env::filter::VarFilter;
 use crate::error::Result;
 use clap::{Parser, Subcommand};
+use target::Target;
 
 #[derive(Parser)]
 #[command(name = "envcipher")]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    /// Env file to operate on instead of searching for .env.
+    #[arg(short = 'f', long, global = true, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Run as if started in DIR.
+    #[arg(short = 'C', long, global = true, value_name = "DIR")]
+    pub dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -23,24 +50,129 @@ pub enum Commands {
     Init,
 
     /// Encrypt .env.
-    Lock,
+    Lock {
+        /// Lock every project under the current directory.
+        #[arg(long)]
+        all: bool,
+
+        /// Restore the file mode it had before `unlock` made it private.
+        #[arg(long)]
+        keep_mode: bool,
+    },
 
     /// Decrypt .env.
-    Unlock,
+    Unlock {
+        /// Unlock every project under the current directory.
+        #[arg(long)]
+        all: bool,
 
-    /// Show status.
-    Status,
+        /// Re-lock automatically after DURATION (e.g. 30m, 1h) or on logout.
+        #[arg(long = "for", value_name = "DURATION", value_parser = humantime::parse_duration)]
+        relock_after: Option<Duration>,
+    },
+
+    /// Show status. Exits 2 if plaintext is on disk, 3 if the key is missing, 4 if the
+    /// status could not be determined (e.g. the credential store is unreachable).
+    Status {
+        /// Report every project under the current directory.
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Print machine-readable JSON.
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Edit encrypted .env.
     Edit,
 
     /// Run command with decrypted env vars.
     Run {
+        /// Mask secret values in the command's output as ***KEY_NAME***.
+        #[arg(long)]
+        redact: bool,
+
+        /// Keep variables that are already set instead of overriding them.
+        #[arg(long)]
+        no_override: bool,
+
+        /// Start from an empty environment (keeping PATH, HOME and a few basics).
+        #[arg(long)]
+        clean: bool,
+
+        /// Only pass env file variables matching these globs (e.g. 'DB_*').
+        #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Leave out env file variables matching these globs.
+        #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Restart the command when the env file changes.
+        #[arg(long)]
+        watch: bool,
+
+        /// Refuse to start unless the variables match .env.schema/.env.example.
+        #[arg(long)]
+        check: bool,
+
         /// Command to run.
         #[arg(last = true, required = true)]
         args: Vec<String>,
     },
 
+    /// Write .env.example with every key and placeholder values.
+    Example {
+        /// Only verify that .env.example is up to date; exits non-zero if not.
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Validate variables against .env.schema (or .env.example).
+    Check {
+        /// Schema file to use instead of the one next to the env file.
+        #[arg(long, value_name = "FILE")]
+        schema: Option<PathBuf>,
+    },
+
+    /// Report duplicate keys, bad quoting and other formatting problems.
+    Lint {
+        /// Rewrite the file with the fixable problems resolved.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Open $SHELL with the variables loaded (ENVCIPHER_ACTIVE marks it).
+    Shell,
+
+    /// Show variables changed since a git revision, file or backup.
+    Diff {
+        /// Git revision (default HEAD) or file to compare against.
+        #[arg(value_name = "REV|FILE")]
+        against: Option<String>,
+
+        /// Compare against a backup from `history` instead.
+        #[arg(long, value_name = "N", conflicts_with = "against")]
+        backup: Option<usize>,
+
+        /// Print values instead of masking them.
+        #[arg(long)]
+        show_values: bool,
+    },
+
+    /// List enciphered backups of .env.
+    History,
+
+    /// Roll .env back to a backup from `history`.
+    Restore {
+        /// Backup number as shown by `history` (1 = most recent).
+        #[arg(required = true)]
+        number: usize,
+    },
+
+    /// Diagnose keychain, key and file problems.
+    Doctor,
+
     /// Export key for sharing.
     ExportKey,
 
@@ -50,6 +182,41 @@ pub enum Commands {
         #[arg(required = true)]
         key: String,
     },
+
+    /// Cache keys in a background agent to avoid repeated keychain prompts.
+    Agent {
+        #[command(subcommand)]
+        action: AgentAction,
+    },
+
+    /// Background watcher started by `unlock --for`.
+    #[command(name = autolock::WATCHER_COMMAND, hide = true)]
+    Autolock {
+        #[arg(long, value_parser = humantime::parse_duration)]
+        after: Duration,
+
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentAction {
+    /// Start the agent in the background.
+    Start {
+        /// How long each key stays cached (e.g. 15m, 2h).
+        #[arg(long, value_name = "DURATION", default_value = "15m", value_parser = humantime::parse_duration)]
+        ttl: Duration,
+
+        /// Stay in the foreground instead of detaching.
+        #[arg(long)]
+        foreground: bool,
+    },
+
+    /// Wipe cached keys and stop the agent.
+    Stop,
+
+    /// Show whether the agent is running.
+    Status,
 }
 
 pub fn execute<I, T>(args: I) -> Result<()>
@@ -58,15 +225,56 @@ where
     T: Into<std::ffi::OsString> + Clone,
 {
     let cli = Cli::parse_from(args);
+    let target = Target::new(cli.file, cli.dir);
+
+    // Every command may hold a key or plaintext; keep them out of swap and core dumps.
+    let _memory_guard = MemoryGuard::engage();
 
     match cli.command {
-        Commands::Init => init::run(),
-        Commands::Lock => lock::run(),
-        Commands::Unlock => unlock::run(),
-        Commands::Status => status::run(),
-        Commands::Edit => edit::run(),
-        Commands::Run { args } => run::run(args),
-        Commands::ExportKey => key::export(),
-        Commands::ImportKey { key } => key::import(&key),
+        Commands::Init => init::run(&target),
+        Commands::Lock { all, keep_mode } => lock::run(&target, all, keep_mode),
+        Commands::Unlock { all, relock_after } => unlock::run(&target, all, relock_after),
+        Commands::Status { recursive, json } => status::run(&target, recursive, json),
+        Commands::Edit => edit::run(&target),
+        Commands::Run {
+            redact,
+            no_override,
+            clean,
+            only,
+            exclude,
+            watch,
+            check,
+            args,
+        } => {
+            let options = run::RunOptions {
+                redact,
+                no_override,
+                clean,
+                filter: VarFilter::new(only, exclude),
+                watch,
+                check,
+            };
+            run::run(&target, args, &options)
+        }
+        Commands::Example { check } => example::run(&target, check),
+        Commands::Check { schema } => check::run(&target, schema),
+        Commands::Lint { fix } => lint::run(&target, fix),
+        Commands::Shell => shell::run(&target),
+        Commands::Diff {
+            against,
+            backup,
+            show_values,
+        } => diff::run(&target, against, backup, show_values),
+        Commands::History => history::list(&target),
+        Commands::Restore { number } => history::restore(&target, number),
+        Commands::Doctor => doctor::run(&target),
+        Commands::ExportKey => key::export(&target),
+        Commands::ImportKey { key } => key::import(&target, &key),
+        Commands::Agent { action } => match action {
+            AgentAction::Start { ttl, foreground } => agent::start(ttl, foreground),
+            AgentAction::Stop => agent::stop(),
+            AgentAction::Status => agent::status(),
+        },
+        Commands::Autolock { after, file } => autolock::watch(&file, after),
     }
 }
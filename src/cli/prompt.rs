@@ -0,0 +1,39 @@
+use std::io::{self, BufRead, Write};
+
+use crate::error::{EnvcipherError, Result};
+
+/// Asks `question` until one of `options` is picked, returning its key. Returns the last
+/// option (conventionally the safe one, e.g. abort) when stdin is closed.
+pub fn choose(question: &str, options: &[(char, &str)]) -> Result<char> {
+    let labels: Vec<String> = options
+        .iter()
+        .map(|(key, label)| format!("[{}]{}", key, &label[key.len_utf8()..]))
+        .collect();
+
+    let fallback = options.last().map(|(key, _)| *key).unwrap_or('a');
+    let stdin = io::stdin();
+
+    loop {
+        print!("{} {}? ", question, labels.join(", "));
+        io::stdout().flush().map_err(EnvcipherError::Io)?;
+
+        let mut answer = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut answer)
+            .map_err(EnvcipherError::Io)?
+            == 0
+        {
+            println!();
+            return Ok(fallback);
+        }
+
+        let answer = answer.trim().to_lowercase();
+        if let Some((key, _)) = options
+            .iter()
+            .find(|(key, label)| answer == key.to_string() || answer == label.to_lowercase())
+        {
+            return Ok(*key);
+        }
+    }
+}
@@ -1,15 +1,73 @@
-use std::env;
-use std::process::Command;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Duration;
 
+use colored::Colorize;
+
+use crate::cli::check;
+use crate::cli::child::{self, SignalForwarder};
+use crate::cli::target::Target;
 use crate::crypto::aead::aes_decipher;
+use crate::crypto::memory;
+use crate::crypto::secret::SecretString;
+use crate::env::filter::VarFilter;
 use crate::env::parser::{
-    find_env_file, hash_directory_path, is_enciphered, parse_enciphered_file, parse_env_content,
+    EnvVars, hash_directory_path, is_enciphered, parse_enciphered_file, parse_env_content,
     read_env_file,
 };
+use crate::env::redact::Redactor;
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run(args: Vec<String>) -> Result<()> {
+/// Inherited variables kept by `run --clean`.
+pub const CLEAN_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "LANG",
+    "TMPDIR",
+    // Windows needs these to start most programs at all.
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "COMSPEC",
+    "PATHEXT",
+];
+
+/// How often `run --watch` checks the env file.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the env file must stay unchanged before `run --watch` reloads it.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How long a command gets to exit after SIGTERM before it is killed on restart.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How `run` builds the child's environment and handles its output.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Mask secret values in the child's output.
+    pub redact: bool,
+    /// Variables already in the environment win over the env file.
+    pub no_override: bool,
+    /// Start from an empty environment plus [`CLEAN_ENV_ALLOWLIST`].
+    pub clean: bool,
+    /// Which env file variables to pass at all.
+    pub filter: VarFilter,
+    /// Restart the command when the env file changes.
+    pub watch: bool,
+    /// Validate the variables against the schema before starting.
+    pub check: bool,
+}
+
+pub fn run(target: &Target, args: Vec<String>, options: &RunOptions) -> Result<()> {
     if args.is_empty() {
         return Err(EnvcipherError::Io(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -17,46 +75,296 @@ pub fn run(args: Vec<String>) -> Result<()> {
         )));
     }
 
-    let env_vars = load_env_vars()?;
+    if options.watch {
+        return run_watched(target, &args, options);
+    }
 
-    let program = &args[0];
-    let program_args = &args[1..];
+    let env_vars = load_selected(target, options)?;
+    let mut command = build_command(&args, &env_vars, options);
 
-    let mut command = Command::new(program);
-    command.args(program_args);
-    command.envs(env_vars);
+    if options.redact {
+        let forwarder = SignalForwarder::start()?;
+        let running = Running::spawn(command, &env_vars, true)?;
+        forwarder.set_child(running.id());
+        // `exit` skips destructors, so wipe our parsed values now. The copies inside
+        // `command`'s environment are std's and are freed without being overwritten.
+        drop(env_vars);
+        let status = running.wait()?;
+        drop(forwarder);
 
-    // Proxy exit status to parent. On Unix, execvp would replace process entirely,
-    // but Command::spawn is more portable across platforms.
-    let status = command.status().map_err(EnvcipherError::Io)?;
+        std::process::exit(child::exit_code(status));
+    }
 
-    if let Some(code) = status.code() {
-        std::process::exit(code);
-    } else {
-        std::process::exit(1);
+    // Replace this process so signals, exit status and process supervision act on the
+    // command directly. The key was already dropped (and zeroized) by `load_env_vars`.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        Err(EnvcipherError::Io(command.exec()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command.status().map_err(EnvcipherError::Io)?;
+        drop(env_vars);
+        std::process::exit(child::exit_code(status));
+    }
+}
+
+/// Restarts the command whenever the env file's variables change. Exits with the
+/// command's status once it exits on its own (including Ctrl-C).
+fn run_watched(target: &Target, args: &[String], options: &RunOptions) -> Result<()> {
+    let env_path = target.env_file()?;
+
+    let mut env_vars = load_selected(target, options)?;
+    let forwarder = SignalForwarder::start()?;
+    let mut running = Running::spawn(
+        build_command(args, &env_vars, options),
+        &env_vars,
+        options.redact,
+    )?;
+    forwarder.set_child(running.id());
+
+    let mut seen = snapshot(&env_path);
+
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        if let Some(status) = running.try_wait()? {
+            drop(forwarder);
+            drop(env_vars);
+            std::process::exit(child::exit_code(status));
+        }
+
+        let current = snapshot(&env_path);
+        if current == seen {
+            continue;
+        }
+
+        // Editors and `lock` write in bursts; wait for the file to settle.
+        let settled = wait_until_settled(&env_path, current);
+        seen = settled;
+
+        let reloaded = match load_selected(target, options) {
+            Ok(vars) => vars,
+            Err(e) => {
+                eprintln!(
+                    "{} {} changed but could not be loaded ({}); keeping the running command.",
+                    "envcipher:".yellow(),
+                    env_path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+
+        // Re-locking or reformatting alone changes nothing for the command.
+        if reloaded == env_vars {
+            continue;
+        }
+        env_vars = reloaded;
+
+        eprintln!(
+            "{} {} changed, restarting {}",
+            "envcipher:".cyan(),
+            env_path.display(),
+            args[0]
+        );
+
+        running.stop()?;
+        running = Running::spawn(
+            build_command(args, &env_vars, options),
+            &env_vars,
+            options.redact,
+        )?;
+        forwarder.set_child(running.id());
+    }
+}
+
+fn wait_until_settled(env_path: &Path, mut last: Option<SecretString>) -> Option<SecretString> {
+    loop {
+        thread::sleep(WATCH_DEBOUNCE);
+
+        let current = snapshot(env_path);
+        if current == last {
+            return current;
+        }
+        last = current;
+    }
+}
+
+/// The env file's raw contents for change detection; plaintext while it is unlocked.
+fn snapshot(env_path: &Path) -> Option<SecretString> {
+    read_env_file(env_path).ok().map(SecretString::from)
+}
+
+fn build_command(args: &[String], env_vars: &[(String, String)], options: &RunOptions) -> Command {
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    memory::restore_for_child(&mut command);
+
+    if options.clean {
+        command.env_clear();
+        for name in CLEAN_ENV_ALLOWLIST {
+            if let Some(value) = std::env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+    }
+
+    command.envs(env_vars.iter().map(|(key, value)| (key, value)));
+    command
+}
+
+/// Deciphers the env file, enforces the schema when asked to, and applies the filters.
+fn load_selected(target: &Target, options: &RunOptions) -> Result<EnvVars> {
+    let vars = load_env_vars(target)?;
+
+    if options.check {
+        check::enforce(&target.env_file()?, &vars)?;
     }
+
+    Ok(select_env_vars(vars, options))
+}
+
+fn select_env_vars(mut vars: EnvVars, options: &RunOptions) -> EnvVars {
+    let inherited = |name: &str| {
+        std::env::var_os(name).is_some() && (!options.clean || CLEAN_ENV_ALLOWLIST.contains(&name))
+    };
+
+    options.filter.apply(&mut vars);
+    vars.retain(|name| !options.no_override || !inherited(name));
+    vars
+}
+
+/// A spawned command, plus the threads redacting its output when `--redact` is on.
+struct Running {
+    process: Child,
+    output: Vec<thread::JoinHandle<()>>,
+}
+
+impl Running {
+    /// With `redact`, stdout and stderr are piped through a [`Redactor`] each, unless no
+    /// value is long enough to be redacted. Stdin stays attached to the terminal.
+    fn spawn(mut command: Command, env_vars: &[(String, String)], redact: bool) -> Result<Self> {
+        if redact && !Redactor::new(env_vars).is_empty() {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        let mut process = command.spawn().map_err(EnvcipherError::Io)?;
+        let mut output = Vec::new();
+
+        if let Some(source) = process.stdout.take() {
+            let redactor = Redactor::new(env_vars);
+            output.push(thread::spawn(move || {
+                forward_redacted(source, io::stdout(), redactor)
+            }));
+        }
+        if let Some(source) = process.stderr.take() {
+            let redactor = Redactor::new(env_vars);
+            output.push(thread::spawn(move || {
+                forward_redacted(source, io::stderr(), redactor)
+            }));
+        }
+
+        Ok(Self { process, output })
+    }
+
+    fn id(&self) -> u32 {
+        self.process.id()
+    }
+
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        let status = self.process.try_wait().map_err(EnvcipherError::Io)?;
+        if status.is_some() {
+            self.drain();
+        }
+        Ok(status)
+    }
+
+    fn wait(mut self) -> Result<ExitStatus> {
+        let status = self.process.wait().map_err(EnvcipherError::Io)?;
+        self.drain();
+        Ok(status)
+    }
+
+    /// Asks the command to exit (SIGTERM on Unix), killing it if it has not after
+    /// [`STOP_TIMEOUT`].
+    fn stop(mut self) -> Result<ExitStatus> {
+        #[cfg(unix)]
+        {
+            use std::time::Instant;
+
+            // SAFETY: kill has no memory-safety preconditions.
+            unsafe {
+                libc::kill(self.process.id() as libc::pid_t, libc::SIGTERM);
+            }
+
+            let deadline = Instant::now() + STOP_TIMEOUT;
+            while Instant::now() < deadline {
+                if let Some(status) = self.try_wait()? {
+                    return Ok(status);
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        let _ = self.process.kill();
+        self.wait()
+    }
+
+    /// Prints what the command wrote before exiting.
+    fn drain(&mut self) {
+        for forwarder in self.output.drain(..) {
+            let _ = forwarder.join();
+        }
+    }
+}
+
+fn forward_redacted(mut source: impl Read, mut sink: impl Write, mut redactor: Redactor) {
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+
+        if sink.write_all(&redactor.push(&buffer[..read])).is_err() || sink.flush().is_err() {
+            return;
+        }
+    }
+
+    let _ = sink.write_all(&redactor.finish());
+    let _ = sink.flush();
+}
+
+pub(crate) fn load_env_vars(target: &Target) -> Result<EnvVars> {
+    Ok(parse_env_content(&load_plaintext(target)?))
 }
 
-fn load_env_vars() -> Result<Vec<(String, String)>> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+/// The env file's contents, deciphered if locked.
+pub(crate) fn load_plaintext(target: &Target) -> Result<SecretString> {
+    let base_dir = target.base_dir()?;
 
-    let env_path = find_env_file(&current_dir)?;
-    let project_dir = env_path.parent().unwrap_or(&current_dir);
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
 
     let contents = read_env_file(&env_path)?;
     let plaintext = if is_enciphered(&contents) {
         let (nonce, ciphertext) = parse_enciphered_file(&contents)?;
         let dir_hash = hash_directory_path(project_dir);
-        let key = keystore::retrieve_key_from_store(&dir_hash)
-            .map_err(|_| EnvcipherError::NotInitialized)?;
+        let key = keystore::retrieve_key_from_store(&dir_hash)?;
 
         let plaintext_bytes = aes_decipher(&key, &nonce, &ciphertext)?;
-        String::from_utf8(plaintext_bytes).map_err(|_| {
+        SecretString::from_utf8(plaintext_bytes).ok_or_else(|| {
             EnvcipherError::Decipherment("deciphered content is not valid UTF-8".to_string())
         })?
     } else {
-        contents
+        SecretString::from(contents)
     };
 
-    Ok(parse_env_content(&plaintext))
+    Ok(plaintext)
 }
@@ -0,0 +1,76 @@
+use std::process::Command;
+
+use colored::Colorize;
+
+use crate::cli::child::{self, SignalForwarder};
+use crate::cli::run::load_env_vars;
+use crate::cli::target::Target;
+use crate::crypto::memory;
+use crate::error::{EnvcipherError, Result};
+
+/// Set inside `envcipher shell` to the project name, for prompts and nesting checks.
+pub const ACTIVE_ENV: &str = "ENVCIPHER_ACTIVE";
+
+/// Opens `$SHELL` with the env file's variables loaded. Nothing is written to disk.
+/// Our parsed values are wiped once the shell has started, but the copies `Command`
+/// keeps for the child's environment are only freed, not overwritten.
+pub fn run(target: &Target) -> Result<()> {
+    if let Ok(active) = std::env::var(ACTIVE_ENV) {
+        println!(
+            "{} Already inside an envcipher shell for {}.",
+            "Warning:".yellow(),
+            active.cyan()
+        );
+    }
+
+    let env_path = target.env_file()?;
+    let project = env_path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "envcipher".to_string());
+
+    let env_vars = load_env_vars(target)?;
+    let shell = default_shell();
+
+    let mut command = Command::new(&shell);
+    memory::restore_for_child(&mut command);
+    command
+        .envs(env_vars.iter().map(|(key, value)| (key, value)))
+        .env(ACTIVE_ENV, &project);
+
+    println!(
+        "Entering envcipher shell for {} ({} variables). Type {} to leave.",
+        project.cyan(),
+        env_vars.len(),
+        "exit".cyan()
+    );
+
+    let forwarder = SignalForwarder::start()?;
+    let spawned = command.spawn();
+
+    drop(command);
+    drop(env_vars);
+
+    let mut shell_process = spawned.map_err(|e| {
+        EnvcipherError::Io(std::io::Error::new(e.kind(), format!("{}: {}", shell, e)))
+    })?;
+    forwarder.set_child(shell_process.id());
+    let status = shell_process.wait().map_err(EnvcipherError::Io)?;
+    drop(forwarder);
+
+    println!("Left envcipher shell for {}.", project.cyan());
+    std::process::exit(child::exit_code(status));
+}
+
+fn default_shell() -> String {
+    #[cfg(unix)]
+    let (variable, fallback) = ("SHELL", "/bin/sh");
+    #[cfg(not(unix))]
+    let (variable, fallback) = ("COMSPEC", "cmd.exe");
+
+    std::env::var(variable)
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
@@ -1,72 +1,342 @@
-use std::env;
 use std::fs;
+use std::path::PathBuf;
 
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
+use serde::Serialize;
 
-use crate::env::parser::{find_env_file, hash_directory_path, is_enciphered, read_env_file};
+use crate::cli::autolock;
+use crate::cli::target::Target;
+use crate::cli::workspace;
+use crate::crypto::secret::SecretString;
+use crate::env::parser::{
+    FORMAT_VERSION, file_mode, has_corrupted_format, hash_directory_path, is_enciphered,
+    is_readable_by_others, read_env_file,
+};
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run() -> Result<()> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+/// Exit code when plaintext secrets are on disk (unlocked or corrupted file).
+pub const EXIT_UNLOCKED: i32 = 2;
 
-    let marker_path = current_dir.join(".envcipher.json");
-    let initialized = marker_path.exists();
+/// Exit code when the project's key is missing from the credential store.
+pub const EXIT_KEY_MISSING: i32 = 3;
 
-    let env_result = find_env_file(&current_dir);
+/// Exit code when the state could not be determined, e.g. the credential store could
+/// not be reached or the env file could not be read.
+pub const EXIT_UNKNOWN: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    Locked,
+    Unlocked,
+    /// Enciphered and plaintext content mixed in one file.
+    Corrupted,
+}
+
+impl FileState {
+    pub fn of(contents: &str) -> Self {
+        if is_enciphered(contents) {
+            FileState::Locked
+        } else if has_corrupted_format(contents) {
+            FileState::Corrupted
+        } else {
+            FileState::Unlocked
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StatusReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    directory: PathBuf,
+    initialized: bool,
+    env_path: Option<PathBuf>,
+    state: Option<FileState>,
+    key_present: Option<bool>,
+    key_id: Option<String>,
+    format_version: Option<&'static str>,
+    last_modified: Option<String>,
+    /// Octal permission bits, e.g. `"600"` (Unix only).
+    mode: Option<String>,
+    /// Why an `unlock --for` watcher could not re-lock the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    autolock_failed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl StatusReport {
+    fn new(directory: PathBuf, initialized: bool) -> Self {
+        Self {
+            project: None,
+            directory,
+            initialized,
+            env_path: None,
+            state: None,
+            key_present: None,
+            key_id: None,
+            format_version: None,
+            last_modified: None,
+            mode: None,
+            autolock_failed: None,
+            error: None,
+        }
+    }
+
+    fn plaintext_readable_by_others(&self) -> bool {
+        let plaintext = matches!(self.state, Some(FileState::Unlocked | FileState::Corrupted));
+        let mode = self
+            .mode
+            .as_deref()
+            .and_then(|mode| u32::from_str_radix(mode, 8).ok());
+
+        plaintext && mode.is_some_and(is_readable_by_others)
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.initialized {
+            return 0;
+        }
+
+        match (self.state, self.key_present) {
+            (Some(FileState::Unlocked | FileState::Corrupted), _) => EXIT_UNLOCKED,
+            (_, Some(false)) => EXIT_KEY_MISSING,
+            _ if self.error.is_some() => EXIT_UNKNOWN,
+            _ => 0,
+        }
+    }
+}
+
+pub fn run(target: &Target, recursive: bool, json: bool) -> Result<()> {
+    if recursive {
+        return run_recursive(target, json);
+    }
+
+    let report = build_report(target)?;
+
+    if json {
+        print_json(&report)?;
+    } else {
+        print_report(&report);
+    }
+
+    exit_with(report.exit_code())
+}
+
+fn run_recursive(target: &Target, json: bool) -> Result<()> {
+    workspace::reject_file_option(target, "--recursive")?;
+
+    let root = target.base_dir()?;
+    let mut reports = Vec::new();
+
+    // A project that cannot be inspected gets an error row instead of hiding the rest.
+    for project in workspace::discover_projects(&root)? {
+        let mut report = build_report(&project.target).unwrap_or_else(|e| {
+            let directory = project
+                .target
+                .base_dir()
+                .unwrap_or_else(|_| root.join(&project.name));
+            let mut report = StatusReport::new(directory, true);
+            report.error = Some(e.to_string());
+            report
+        });
+        report.project = Some(project.name);
+        reports.push(report);
+    }
+
+    let codes: Vec<i32> = reports.iter().map(StatusReport::exit_code).collect();
+    let exit_code = [EXIT_UNLOCKED, EXIT_KEY_MISSING, EXIT_UNKNOWN]
+        .into_iter()
+        .find(|code| codes.contains(code))
+        .unwrap_or(0);
+
+    if json {
+        print_json(&reports)?;
+        return exit_with(exit_code);
+    }
 
     println!("{}", "envcipher status".bold());
     println!("────────────────────────────────────────");
-    println!("Directory:   {}", current_dir.display());
+    println!("Root:        {}", root.display());
+    println!();
+
+    if reports.is_empty() {
+        println!("No envcipher projects found.");
+        return Ok(());
+    }
+
+    let name_width = reports
+        .iter()
+        .filter_map(|report| report.project.as_ref())
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("PROJECT".len());
+
+    println!(
+        "{:<name_width$}  {:<10}  {}",
+        "PROJECT".bold(),
+        "STATUS".bold(),
+        "KEY".bold()
+    );
+
+    for report in &reports {
+        println!(
+            "{:<name_width$}  {}  {}",
+            report.project.as_deref().unwrap_or_default(),
+            state_cell(report),
+            key_cell(report)
+        );
+    }
+
+    exit_with(exit_code)
+}
 
-    if initialized {
+fn build_report(target: &Target) -> Result<StatusReport> {
+    let directory = target.base_dir()?;
+    let initialized = target.marker_path()?.exists();
+
+    let mut report = StatusReport::new(directory, initialized);
+
+    let Ok(env_path) = target.env_file() else {
+        return Ok(report);
+    };
+
+    match read_env_file(&env_path).map(SecretString::from) {
+        Ok(contents) => {
+            let state = FileState::of(&contents);
+            report.state = Some(state);
+            report.format_version = (state == FileState::Locked).then_some(FORMAT_VERSION);
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+
+    if let Ok(metadata) = fs::metadata(&env_path)
+        && let Ok(modified) = metadata.modified()
+    {
+        report.last_modified = Some(humantime::format_rfc3339_seconds(modified).to_string());
+    }
+
+    report.mode = file_mode(&env_path).map(|mode| format!("{:o}", mode));
+    report.autolock_failed = autolock::failure(&env_path);
+
+    let project_dir = env_path.parent().unwrap_or(&report.directory);
+    let dir_hash = hash_directory_path(project_dir);
+    match keystore::key_exists(&dir_hash) {
+        Ok(present) => {
+            report.key_present = Some(present);
+            report.key_id = present.then(|| dir_hash[..8].to_string());
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+
+    report.env_path = Some(env_path);
+    Ok(report)
+}
+
+fn print_report(report: &StatusReport) {
+    println!("{}", "envcipher status".bold());
+    println!("────────────────────────────────────────");
+    println!("Directory:   {}", report.directory.display());
+
+    if report.initialized {
         println!("Initialized: {}", "Yes".green());
     } else {
         println!("Initialized: {}", "No".yellow());
         println!("Run {} to initialize.", "envcipher init".cyan());
-        return Ok(());
+        return;
     }
 
-    match env_result {
-        Ok(env_path) => {
-            let project_dir = env_path.parent().unwrap_or(&current_dir);
-
-            match read_env_file(&env_path) {
-                Ok(contents) => {
-                    if is_enciphered(&contents) {
-                        println!("Status:      {}", "Locked (enciphered)".green());
-                    } else {
-                        println!("Status:      {}", "Unlocked (EXPOSED)".red().bold());
-                    }
-
-                    if let Ok(metadata) = fs::metadata(&env_path)
-                        && let Ok(modified) = metadata.modified()
-                    {
-                        println!("Modified:    {:?}", modified);
-                    }
-
-                    let dir_hash = hash_directory_path(project_dir);
-                    match keystore::key_exists(&dir_hash) {
-                        Ok(true) => {
-                            println!("Key ID:      {}", &dir_hash[..8]);
-                        }
-                        Ok(false) => {
-                            println!("Key:         {}", "Not found in credential store".red());
-                        }
-                        Err(_) => {
-                            println!("Key:         {}", "Error checking credential store".red());
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("Env file:    Error reading: {}", e);
-                }
-            }
+    let Some(env_path) = &report.env_path else {
+        println!("Env file:    {}", "Not found".yellow());
+        return;
+    };
+
+    println!("Env file:    {}", env_path.display());
+
+    match report.state {
+        Some(FileState::Locked) => println!("Status:      {}", "Locked (enciphered)".green()),
+        Some(FileState::Unlocked) => {
+            println!("Status:      {}", "Unlocked (EXPOSED)".red().bold())
         }
-        Err(_) => {
-            println!("Env file:    {}", "Not found".yellow());
+        Some(FileState::Corrupted) => {
+            println!("Status:      {}", "Corrupted (mixed content)".red().bold())
         }
+        None => {}
+    }
+
+    if let Some(modified) = &report.last_modified {
+        println!("Modified:    {}", modified);
     }
 
+    if let Some(mode) = &report.mode {
+        if report.plaintext_readable_by_others() {
+            println!(
+                "Mode:        {} {}",
+                mode.red(),
+                "(plaintext readable by group/others; run `chmod 600`)".yellow()
+            );
+        } else {
+            println!("Mode:        {}", mode);
+        }
+    }
+
+    if let Some(reason) = &report.autolock_failed {
+        println!(
+            "Auto-lock:   {} {}",
+            "failed;".red().bold(),
+            format!("run `envcipher lock` ({})", reason).yellow()
+        );
+    }
+
+    match (report.key_present, &report.key_id) {
+        (Some(true), Some(key_id)) => println!("Key ID:      {}", key_id),
+        (Some(false), _) => println!("Key:         {}", "Not found in credential store".red()),
+        _ => {}
+    }
+
+    if let Some(error) = &report.error {
+        println!("Error:       {}", error.red());
+    }
+}
+
+fn state_cell(report: &StatusReport) -> ColoredString {
+    match report.state {
+        Some(FileState::Locked) => pad("Locked").green(),
+        Some(FileState::Unlocked) => pad("Unlocked").red().bold(),
+        Some(FileState::Corrupted) => pad("Corrupted").red().bold(),
+        None if report.env_path.is_some() || report.error.is_some() => pad("Unreadable").red(),
+        None => pad("No .env").yellow(),
+    }
+}
+
+fn key_cell(report: &StatusReport) -> ColoredString {
+    match (report.key_present, &report.key_id) {
+        (Some(true), Some(key_id)) => key_id.normal(),
+        (Some(false), _) => "missing".red(),
+        _ if report.env_path.is_none() && report.error.is_none() => "-".dimmed(),
+        _ => "error".red(),
+    }
+}
+
+fn pad(label: &str) -> String {
+    format!("{:<10}", label)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| EnvcipherError::InvalidFormat(e.to_string()))?;
+    println!("{}", json);
     Ok(())
 }
+
+/// Status reports problems through the exit code rather than as errors; the caller
+/// turns [`EnvcipherError::Exit`] into the process status.
+fn exit_with(code: i32) -> Result<()> {
+    match code {
+        0 => Ok(()),
+        code => Err(EnvcipherError::Exit(code)),
+    }
+}
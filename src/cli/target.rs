@@ -0,0 +1,130 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::env::parser::find_env_file;
+use crate::error::{EnvcipherError, Result};
+
+/// Project marker written by `envcipher init`.
+pub const MARKER_FILENAME: &str = ".envcipher.json";
+
+const DEFAULT_ENV_FILENAME: &str = ".env";
+
+/// Contents of `.envcipher.json`. Fields are optional so hand-edited markers still load.
+#[derive(Debug, Default, Deserialize)]
+pub struct Marker {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+impl Marker {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(EnvcipherError::Io)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| EnvcipherError::InvalidFormat(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// File or directory a command operates on, taken from the global `--file`/`--dir` options.
+#[derive(Debug, Clone, Default)]
+pub struct Target {
+    file: Option<PathBuf>,
+    dir: Option<PathBuf>,
+}
+
+impl Target {
+    pub fn new(file: Option<PathBuf>, dir: Option<PathBuf>) -> Self {
+        Self { file, dir }
+    }
+
+    pub fn has_file(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// `--dir` resolved against the current directory, or the current directory itself.
+    pub fn base_dir(&self) -> Result<PathBuf> {
+        let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+
+        match &self.dir {
+            Some(dir) => canonicalize(&current_dir.join(dir)),
+            None => Ok(current_dir),
+        }
+    }
+
+    /// Existing env file: `--file` if given, otherwise the nearest `.env` above the base dir.
+    pub fn env_file(&self) -> Result<PathBuf> {
+        let base_dir = self.base_dir()?;
+
+        match &self.file {
+            Some(file) => {
+                let path = absolute_file_path(&base_dir, file)?;
+                if !path.is_file() {
+                    return Err(EnvcipherError::EnvFileNotFound(path));
+                }
+                Ok(path)
+            }
+            None => find_env_file(&base_dir),
+        }
+    }
+
+    /// Where the env file is or would be created, for commands that work before it exists.
+    pub fn env_file_path(&self) -> Result<PathBuf> {
+        let base_dir = self.base_dir()?;
+
+        match &self.file {
+            Some(file) => absolute_file_path(&base_dir, file),
+            None => Ok(
+                find_env_file(&base_dir).unwrap_or_else(|_| base_dir.join(DEFAULT_ENV_FILENAME))
+            ),
+        }
+    }
+
+    /// With `--file` the marker sits next to that file, otherwise in the base dir.
+    pub fn marker_path(&self) -> Result<PathBuf> {
+        let marker_dir = match &self.file {
+            Some(_) => {
+                let env_path = self.env_file_path()?;
+                env_path
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or(self.base_dir()?)
+            }
+            None => self.base_dir()?,
+        };
+
+        Ok(marker_dir.join(MARKER_FILENAME))
+    }
+}
+
+/// Canonicalizes only the parent so a symlinked env file keeps its own project identity.
+fn absolute_file_path(base_dir: &Path, file: &Path) -> Result<PathBuf> {
+    let path = base_dir.join(file);
+
+    let file_name = path.file_name().ok_or_else(|| {
+        EnvcipherError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        ))
+    })?;
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => canonicalize(parent)?,
+        _ => base_dir.to_path_buf(),
+    };
+
+    Ok(parent.join(file_name))
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    fs::canonicalize(path).map_err(|e| {
+        EnvcipherError::Io(io::Error::new(
+            e.kind(),
+            format!("{}: {}", path.display(), e),
+        ))
+    })
+}
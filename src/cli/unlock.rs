@@ -1,20 +1,73 @@
-use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use colored::Colorize;
 
+use crate::cli::autolock;
+use crate::cli::target::Target;
+use crate::cli::workspace;
 use crate::crypto::aead::aes_decipher;
+use crate::crypto::secret::SecretString;
+use crate::env::backup::remember_unlocked_ciphertext;
+use crate::env::filelock::EnvFileLock;
 use crate::env::parser::{
-    find_env_file, has_corrupted_format, hash_directory_path, is_enciphered, parse_enciphered_file,
-    read_env_file, write_to_env_file,
+    PRIVATE_MODE, file_mode, has_corrupted_format, hash_directory_path, is_enciphered,
+    parse_enciphered_file, read_env_file, remember_locked_mode, write_plaintext_env_file,
 };
 use crate::error::{EnvcipherError, Result};
 use crate::keystore;
 
-pub fn run() -> Result<()> {
-    let current_dir = env::current_dir().map_err(EnvcipherError::Io)?;
+pub fn run(target: &Target, all: bool, relock_after: Option<Duration>) -> Result<()> {
+    if all {
+        return workspace::for_each_project(target, |project| match unlock_target(project) {
+            Ok(env_path) => match relock_after {
+                Some(after) => {
+                    autolock::spawn(&env_path, after)?;
+                    Ok(format!(
+                        "unlocked, re-locks in {}",
+                        humantime::format_duration(after)
+                    ))
+                }
+                None => Ok("unlocked".to_string()),
+            },
+            Err(EnvcipherError::NotEnciphered) => Ok("already unlocked".to_string()),
+            Err(e) => Err(e),
+        });
+    }
+
+    let env_path = unlock_target(target)?;
+
+    println!("{}", "Unlocked!".green().bold());
+    println!("File: {}", env_path.display());
+    println!();
+
+    match relock_after {
+        Some(after) => {
+            let pid = autolock::spawn(&env_path, after)?;
+            println!(
+                "Plaintext secrets are exposed on disk. Re-locking in {} or on logout (watcher pid {}).",
+                humantime::format_duration(after).to_string().cyan(),
+                pid
+            );
+            println!("Log: {}", autolock::log_path(&env_path)?.display());
+        }
+        None => println!(
+            "{} Plaintext secrets are exposed on disk. Run {} when done.",
+            "Warning:".yellow(),
+            "envcipher lock".cyan()
+        ),
+    }
+
+    Ok(())
+}
+
+fn unlock_target(target: &Target) -> Result<PathBuf> {
+    let base_dir = target.base_dir()?;
 
-    let env_path = find_env_file(&current_dir)?;
-    let project_dir = env_path.parent().unwrap_or(&current_dir);
+    let env_path = target.env_file()?;
+    let project_dir = env_path.parent().unwrap_or(&base_dir);
+
+    let _lock = EnvFileLock::acquire(&env_path)?;
 
     let contents = read_env_file(&env_path)?;
 
@@ -23,11 +76,12 @@ pub fn run() -> Result<()> {
     }
 
     let dir_hash = hash_directory_path(project_dir);
-    let key =
-        keystore::retrieve_key_from_store(&dir_hash).map_err(|_| EnvcipherError::NotInitialized)?;
+    let key = keystore::retrieve_key_from_store(&dir_hash)?;
+
+    remember_unlocked_ciphertext(&env_path, &contents)?;
 
     // Recursively decrypt in case of nested encipherment (from mixed content being locked).
-    let mut plaintext_str = contents;
+    let mut plaintext_str = SecretString::new(contents);
     let mut decipherment_count = 0;
     const MAX_DECIPHERMENT_ATTEMPTS: usize = 10; // Prevent infinite loops
 
@@ -35,13 +89,13 @@ pub fn run() -> Result<()> {
         if is_enciphered(&plaintext_str) {
             let (nonce, ciphertext) = parse_enciphered_file(&plaintext_str)?;
             let plaintext = aes_decipher(&key, &nonce, &ciphertext)?;
-            plaintext_str = String::from_utf8(plaintext).map_err(|_| {
+            plaintext_str = SecretString::from_utf8(plaintext).ok_or_else(|| {
                 EnvcipherError::Decipherment("deciphered content is not valid UTF-8".to_string())
             })?;
             decipherment_count += 1;
         } else if has_corrupted_format(&plaintext_str) {
             // Mixed content.
-            let mut deciphered_lines = Vec::new();
+            let mut deciphered_lines: Vec<SecretString> = Vec::new();
             let mut found_enciphered = false;
 
             for line in plaintext_str.lines() {
@@ -51,8 +105,8 @@ pub fn run() -> Result<()> {
                     match parse_enciphered_file(line) {
                         Ok((nonce, ciphertext)) => match aes_decipher(&key, &nonce, &ciphertext) {
                             Ok(plaintext) => {
-                                let deciphered_line =
-                                    String::from_utf8(plaintext).map_err(|_| {
+                                let deciphered_line = SecretString::from_utf8(plaintext)
+                                    .ok_or_else(|| {
                                         EnvcipherError::Decipherment(
                                             "deciphered content is not valid UTF-8".to_string(),
                                         )
@@ -60,17 +114,24 @@ pub fn run() -> Result<()> {
                                 deciphered_lines.push(deciphered_line);
                                 found_enciphered = true;
                             }
-                            Err(_) => deciphered_lines.push(line.to_string()),
+                            Err(_) => deciphered_lines.push(SecretString::from(line)),
                         },
-                        Err(_) => deciphered_lines.push(line.to_string()),
+                        Err(_) => deciphered_lines.push(SecretString::from(line)),
                     }
                 } else if !line.is_empty() {
-                    deciphered_lines.push(line.to_string());
+                    deciphered_lines.push(SecretString::from(line));
                 }
             }
 
             if found_enciphered {
-                plaintext_str = deciphered_lines.join("\n") + "\n";
+                // Sized up front so no partial copies are left behind by reallocation.
+                let mut joined =
+                    String::with_capacity(deciphered_lines.iter().map(|line| line.len() + 1).sum());
+                for line in &deciphered_lines {
+                    joined.push_str(line);
+                    joined.push('\n');
+                }
+                plaintext_str = SecretString::new(joined);
                 decipherment_count += 1;
             } else {
                 break; // No more enciphered content found
@@ -88,16 +149,14 @@ pub fn run() -> Result<()> {
         );
     }
 
-    write_to_env_file(&env_path, &plaintext_str)?;
-
-    println!("{}", "Unlocked!".green().bold());
-    println!("File: {}", env_path.display());
-    println!();
-    println!(
-        "{} Plaintext secrets are exposed on disk. Run {} when done.",
-        "Warning:".yellow(),
-        "envcipher lock".cyan()
-    );
+    // Plaintext is private to the owner; remember the old mode for `lock --keep-mode`.
+    let locked_mode = file_mode(&env_path);
+    write_plaintext_env_file(&env_path, &plaintext_str)?;
+    if let Some(mode) = locked_mode
+        && mode != PRIVATE_MODE
+    {
+        remember_locked_mode(&env_path, mode)?;
+    }
 
-    Ok(())
+    Ok(env_path)
 }
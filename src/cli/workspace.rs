@@ -0,0 +1,125 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::cli::target::{MARKER_FILENAME, Target};
+use crate::error::{EnvcipherError, Result};
+
+/// Directory names never descended into while searching for projects.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor"];
+
+/// An initialized project found below the workspace root.
+pub struct Project {
+    /// Path relative to the workspace root, used in reports.
+    pub name: String,
+    pub target: Target,
+}
+
+/// Every directory under `root` (including `root`) holding an `.envcipher.json` marker.
+pub fn discover_projects(root: &Path) -> Result<Vec<Project>> {
+    let mut dirs = Vec::new();
+    collect_project_dirs(root, &mut dirs)?;
+    dirs.sort();
+
+    Ok(dirs
+        .into_iter()
+        .map(|dir| Project {
+            name: display_name(root, &dir),
+            // Pin the env file to the project dir so a service without one never
+            // falls back to a parent's .env.
+            target: Target::new(Some(PathBuf::from(".env")), Some(dir)),
+        })
+        .collect())
+}
+
+/// Runs `action` on every project under the target's base dir, reporting each outcome
+/// instead of stopping at the first failure.
+pub fn for_each_project<F>(target: &Target, action: F) -> Result<()>
+where
+    F: Fn(&Target) -> Result<String>,
+{
+    reject_file_option(target, "--all")?;
+
+    let root = target.base_dir()?;
+    let projects = discover_projects(&root)?;
+
+    if projects.is_empty() {
+        println!("No envcipher projects found under {}", root.display());
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for project in &projects {
+        match action(&project.target) {
+            Ok(outcome) => println!("{} {}: {}", "✓".green(), project.name, outcome),
+            Err(e) => {
+                failed += 1;
+                println!("{} {}: {}", "✗".red(), project.name, e);
+            }
+        }
+    }
+
+    println!();
+    if failed > 0 {
+        return Err(EnvcipherError::ProjectsFailed {
+            failed,
+            total: projects.len(),
+        });
+    }
+
+    println!("{} {} project(s)", "Done:".green().bold(), projects.len());
+    Ok(())
+}
+
+/// Workspace-wide commands pick each project's `.env` themselves, so `--file` has no
+/// meaning next to `flag`.
+pub fn reject_file_option(target: &Target, flag: &str) -> Result<()> {
+    if target.has_file() {
+        return Err(EnvcipherError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("--file cannot be combined with {}", flag),
+        )));
+    }
+    Ok(())
+}
+
+fn collect_project_dirs(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    if dir.join(MARKER_FILENAME).is_file() {
+        found.push(dir.to_path_buf());
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Ok(()),
+        Err(e) => return Err(EnvcipherError::Io(e)),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(EnvcipherError::Io)?;
+
+        // file_type() does not follow symlinks, which keeps the walk free of cycles.
+        if !entry.file_type().map_err(EnvcipherError::Io)?.is_dir() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+
+        collect_project_dirs(&entry.path(), found)?;
+    }
+
+    Ok(())
+}
+
+fn display_name(root: &Path, dir: &Path) -> String {
+    match dir.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => dir.display().to_string(),
+    }
+}
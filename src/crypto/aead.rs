@@ -3,6 +3,7 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
 };
 use rand::RngCore;
+use zeroize::Zeroizing;
 
 use crate::crypto::secret::SecretKey;
 use crate::error::{EnvcipherError, Result};
@@ -15,9 +16,9 @@ pub const NONCE_LEN: usize = 12;
 
 // /// Generate cryptographically secure random key.
 pub fn generate_key() -> SecretKey {
-    let mut key = [0u8; KEY_LEN];
-    rand::rng().fill_bytes(&mut key);
-    SecretKey::new(key)
+    let mut key = SecretKey::new([0u8; KEY_LEN]);
+    rand::rng().fill_bytes(key.as_mut_bytes());
+    key
 }
 
 pub fn aes_encipher(key: &SecretKey, plaintext: &[u8]) -> Result<(Vec<u8>, [u8; NONCE_LEN])> {
@@ -41,12 +42,13 @@ pub fn generate_nonce() -> [u8; NONCE_LEN] {
     nonce
 }
 
-/// Fails if tag validation check fails (tampering detected).
+/// Fails if tag validation check fails (tampering detected). The plaintext is wiped
+/// when dropped.
 pub fn aes_decipher(
     key: &SecretKey,
     nonce: &[u8; NONCE_LEN],
     ciphertext: &[u8],
-) -> Result<Vec<u8>> {
+) -> Result<Zeroizing<Vec<u8>>> {
     let cipher = Aes256Gcm::new_from_slice(key.as_bytes())
         .map_err(|e| EnvcipherError::Decipherment(e.to_string()))?;
 
@@ -54,5 +56,6 @@ pub fn aes_decipher(
 
     cipher
         .decrypt(nonce, ciphertext)
+        .map(Zeroizing::new)
         .map_err(|_| EnvcipherError::Decipherment("authentication failed".to_string()))
 }
@@ -0,0 +1,313 @@
+//! Protection for keys and deciphered plaintext. Their buffers are locked into RAM
+//! one allocation at a time wherever `mlock` exists. On Linux, [`MemoryGuard`] also
+//! keeps the process out of core dumps (and ptrace by other processes of the same
+//! user), and locks all memory when `RLIMIT_MEMLOCK` allows.
+
+#[cfg(unix)]
+use std::collections::BTreeMap;
+use std::process::Command;
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set while `mlockall` is in effect, so freeing one secret does not unlock pages
+/// that the rest of the process shares.
+#[cfg(unix)]
+static ALL_LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Set once any secret buffer could not be locked, e.g. over `RLIMIT_MEMLOCK`.
+static REGION_LOCK_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// How many live secret buffers use each locked page. Locks do not nest, so a page is
+/// only unlocked once the last of them is freed.
+#[cfg(unix)]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Core-dump limit from before the first live guard lowered it, so spawned commands
+/// get theirs back, and how many guards are live. Only the last one to drop restores it.
+#[cfg(target_os = "linux")]
+static SAVED_CORE_LIMIT: Mutex<(usize, Option<(u64, u64)>)> = Mutex::new((0, None));
+
+/// Keeps secrets held by this process out of crash dumps until dropped, and all of its
+/// memory out of swap when there is no lock limit to run into.
+///
+/// The CLI engages one for every command. Library users opt in:
+///
+/// ```no_run
+/// let _guard = envcipher::crypto::memory::MemoryGuard::engage();
+/// // ... decipher and use secrets ...
+/// ```
+///
+/// Each protection is best effort; the accessors report which ones took effect.
+#[derive(Debug)]
+pub struct MemoryGuard {
+    all_locked: bool,
+    dumps_disabled: bool,
+    previous_dumpable: Option<i32>,
+}
+
+impl MemoryGuard {
+    #[cfg(target_os = "linux")]
+    pub fn engage() -> Self {
+        let core_off = {
+            let mut saved = SAVED_CORE_LIMIT.lock().unwrap_or_else(|e| e.into_inner());
+            saved.0 += 1;
+            // A nested guard would only see the limit the first one already lowered.
+            if saved.0 == 1 {
+                let previous_core = get_limit(libc::RLIMIT_CORE);
+                if previous_core.is_some_and(|(_, max)| set_limit(libc::RLIMIT_CORE, 0, max)) {
+                    saved.1 = previous_core;
+                }
+            }
+            saved.1.is_some()
+        };
+
+        // SAFETY: prctl with these options only reads or sets a flag on this process.
+        let previous_dumpable = unsafe { libc::prctl(libc::PR_GET_DUMPABLE) };
+        let undumpable = unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } == 0;
+
+        // Locking future mappings makes allocations fail once RLIMIT_MEMLOCK is reached,
+        // so only do it when there is no limit to reach.
+        // Secret buffers are locked individually either way; this is an extra.
+        let all_locked = can_lock_everything() && lock_all();
+        if all_locked {
+            ALL_LOCKED.store(true, Ordering::Relaxed);
+        }
+
+        Self {
+            all_locked,
+            dumps_disabled: core_off || undumpable,
+            previous_dumpable: (undumpable && previous_dumpable >= 0).then_some(previous_dumpable),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn engage() -> Self {
+        Self {
+            all_locked: false,
+            dumps_disabled: false,
+            previous_dumpable: None,
+        }
+    }
+
+    /// Whether keys and deciphered plaintext are kept out of swap: either all memory
+    /// is locked, or every secret buffer so far was locked on its own.
+    pub fn memory_locked(&self) -> bool {
+        self.all_locked || (cfg!(unix) && !REGION_LOCK_FAILED.load(Ordering::Relaxed))
+    }
+
+    /// Whether all of this process's memory is locked into RAM with `mlockall`.
+    pub fn all_memory_locked(&self) -> bool {
+        self.all_locked
+    }
+
+    /// Whether core dumps of this process are disabled.
+    pub fn dumps_disabled(&self) -> bool {
+        self.dumps_disabled
+    }
+}
+
+impl Drop for MemoryGuard {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        {
+            // SAFETY: undoes what `engage` did; none of these touch Rust-managed memory.
+            unsafe {
+                if self.all_locked {
+                    // Secrets that outlive the guard keep their own locks.
+                    let locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+                    ALL_LOCKED.store(false, Ordering::Relaxed);
+                    libc::munlockall();
+                    for page in locked.keys() {
+                        libc::mlock(*page as *const libc::c_void, page_size());
+                    }
+                }
+                if let Some(dumpable) = self.previous_dumpable {
+                    libc::prctl(libc::PR_SET_DUMPABLE, dumpable as libc::c_ulong);
+                }
+            }
+
+            let mut saved = SAVED_CORE_LIMIT.lock().unwrap_or_else(|e| e.into_inner());
+            saved.0 = saved.0.saturating_sub(1);
+            if saved.0 == 0
+                && let Some((cur, max)) = saved.1.take()
+            {
+                set_limit(libc::RLIMIT_CORE, cur, max);
+            }
+        }
+    }
+}
+
+/// Locks the `len` bytes at `ptr` into RAM. Failure is recorded for
+/// [`MemoryGuard::memory_locked`] and otherwise only means the pages may be swapped.
+pub(crate) fn lock_region(ptr: *const u8, len: usize) {
+    #[cfg(unix)]
+    for page in pages(ptr, len) {
+        let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        let count = locked.entry(page).or_insert(0);
+
+        // SAFETY: mlock only changes page attributes; it never reads or writes memory.
+        if *count == 0 && unsafe { libc::mlock(page as *const libc::c_void, page_size()) } != 0 {
+            locked.remove(&page);
+            REGION_LOCK_FAILED.store(true, Ordering::Relaxed);
+            continue;
+        }
+        *count += 1;
+    }
+
+    #[cfg(not(unix))]
+    let _ = (ptr, len);
+}
+
+/// Undoes [`lock_region`]; call it after wiping the region, just before it is freed.
+/// A page stays locked while another secret on it is still alive.
+pub(crate) fn unlock_region(ptr: *const u8, len: usize) {
+    #[cfg(unix)]
+    for page in pages(ptr, len) {
+        let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(count) = locked.get_mut(&page) else {
+            continue;
+        };
+
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&page);
+            if !ALL_LOCKED.load(Ordering::Relaxed) {
+                // SAFETY: munlock only changes page attributes; it never reads or writes memory.
+                unsafe {
+                    libc::munlock(page as *const libc::c_void, page_size());
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (ptr, len);
+}
+
+/// Start addresses of the pages spanned by `len` bytes at `ptr`.
+#[cfg(unix)]
+fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+    let size = page_size();
+    let first = ptr as usize & !(size - 1);
+    let end = if len == 0 { first } else { ptr as usize + len };
+    (first..end).step_by(size)
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+/// Gives a command spawned by this process its own core-dump limit back. Memory locks
+/// and the dumpable flag are reset by `exec` anyway.
+pub fn restore_for_child(command: &mut Command) {
+    #[cfg(target_os = "linux")]
+    if let Some((cur, max)) = SAVED_CORE_LIMIT.lock().unwrap_or_else(|e| e.into_inner()).1 {
+        use std::os::unix::process::CommandExt;
+
+        // SAFETY: setrlimit is async-signal-safe and the closure only captures integers.
+        unsafe {
+            command.pre_exec(move || {
+                set_limit(libc::RLIMIT_CORE, cur, max);
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = command;
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+type Resource = libc::c_int;
+
+#[cfg(target_os = "linux")]
+fn get_limit(resource: Resource) -> Option<(u64, u64)> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit for getrlimit to fill in.
+    (unsafe { libc::getrlimit(resource, &mut limit) } == 0)
+        .then_some((limit.rlim_cur, limit.rlim_max))
+}
+
+#[cfg(target_os = "linux")]
+fn set_limit(resource: Resource, cur: u64, max: u64) -> bool {
+    let limit = libc::rlimit {
+        rlim_cur: cur,
+        rlim_max: max,
+    };
+    // SAFETY: `limit` is a valid rlimit for setrlimit to read.
+    unsafe { libc::setrlimit(resource, &limit) == 0 }
+}
+
+/// `mlockall`, backed out again unless a thread stack's worth of new memory can still
+/// be mapped: with `MCL_FUTURE` every later allocation must be locked as well.
+#[cfg(target_os = "linux")]
+fn lock_all() -> bool {
+    const PROBE_LEN: usize = 8 << 20;
+
+    // SAFETY: mlockall, mmap of a fresh anonymous region, munmap of exactly that region
+    // and munlockall touch no Rust-managed memory.
+    unsafe {
+        if libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) != 0 {
+            return false;
+        }
+
+        let probe = libc::mmap(
+            std::ptr::null_mut(),
+            PROBE_LEN,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if probe == libc::MAP_FAILED {
+            libc::munlockall();
+            return false;
+        }
+        libc::munmap(probe, PROBE_LEN);
+    }
+
+    true
+}
+
+/// Reports whether locking is unlimited: CAP_IPC_LOCK is in effect, or RLIMIT_MEMLOCK
+/// is (after raising the soft limit to the hard one). Being root is not enough, since
+/// containers usually run root without the capability.
+#[cfg(target_os = "linux")]
+fn can_lock_everything() -> bool {
+    if has_ipc_lock_capability() {
+        return true;
+    }
+
+    match get_limit(libc::RLIMIT_MEMLOCK) {
+        Some((cur, _)) if cur == libc::RLIM_INFINITY => true,
+        Some((_, max)) if max == libc::RLIM_INFINITY => set_limit(libc::RLIMIT_MEMLOCK, max, max),
+        _ => false,
+    }
+}
+
+/// Whether CAP_IPC_LOCK is in the effective set (`CapEff` in /proc/self/status).
+#[cfg(target_os = "linux")]
+fn has_ipc_lock_capability() -> bool {
+    const CAP_IPC_LOCK: u32 = 14;
+
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))
+                .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+        })
+        .is_some_and(|caps| caps & (1 << CAP_IPC_LOCK) != 0)
+}
@@ -1,17 +1,183 @@
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use std::fmt;
+use std::ops::Deref;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::crypto::aead::KEY_LEN;
+use crate::crypto::memory::{lock_region, unlock_region};
+use crate::error::{EnvcipherError, Result};
 
-/// A wrapper for the encryption key that zeroizes memory on drop.
-#[derive(Debug, Zeroize, ZeroizeOnDrop)]
-pub struct SecretKey(pub [u8; KEY_LEN]);
+/// A wrapper for the encryption key that zeroizes memory on drop. The bytes live in
+/// their own allocation, locked into RAM. `Debug` shows only its fingerprint and `==`
+/// runs in constant time.
+pub struct SecretKey(Box<[u8; KEY_LEN]>);
 
 impl SecretKey {
-    pub fn new(key: [u8; KEY_LEN]) -> Self {
-        Self(key)
+    pub fn new(mut key: [u8; KEY_LEN]) -> Self {
+        let mut secret = Self::zeroed();
+        secret.0.copy_from_slice(&key);
+        key.zeroize();
+        secret
+    }
+
+    /// Locks the allocation before any key bytes are copied into it.
+    fn zeroed() -> Self {
+        let bytes = Box::new([0u8; KEY_LEN]);
+        lock_region(bytes.as_ptr(), KEY_LEN);
+        Self(bytes)
+    }
+
+    /// Fails unless `bytes` is exactly [`KEY_LEN`] long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != KEY_LEN {
+            return Err(EnvcipherError::InvalidKey(format!(
+                "key must be {} bytes, got {}",
+                KEY_LEN,
+                bytes.len()
+            )));
+        }
+
+        let mut key = Self::zeroed();
+        key.0.copy_from_slice(bytes);
+        Ok(key)
+    }
+
+    /// Decodes the standard base64 form used by `export-key` and the keychain. The
+    /// decoded bytes are wiped once copied.
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = Zeroizing::new(
+            BASE64
+                .decode(encoded.trim())
+                .map_err(|e| EnvcipherError::InvalidKey(format!("invalid base64: {}", e)))?,
+        );
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_base64(&self) -> Zeroizing<String> {
+        Zeroizing::new(BASE64.encode(*self.0))
     }
 
     pub fn as_bytes(&self) -> &[u8] {
+        &*self.0
+    }
+
+    /// For filling a key in place, e.g. after its memory has been locked.
+    pub(crate) fn as_mut_bytes(&mut self) -> &mut [u8; KEY_LEN] {
+        &mut self.0
+    }
+
+    /// Short, non-secret identifier for comparing keys across machines: the first 8
+    /// bytes of the key's SHA-256, as hex pairs.
+    pub fn fingerprint(&self) -> String {
+        Sha256::digest(*self.0)[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&*other.0).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl Zeroize for SecretKey {
+    fn zeroize(&mut self) {
+        (*self.0).zeroize();
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+        unlock_region(self.0.as_ptr(), KEY_LEN);
+    }
+}
+
+impl ZeroizeOnDrop for SecretKey {}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("fingerprint", &self.fingerprint())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Deciphered text, locked into RAM and wiped on drop. Derefs to `str`; `Debug` never
+/// shows the contents.
+#[derive(Default, PartialEq, Eq, Zeroize)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Takes over `text`'s buffer; it is never grown, so locking it once is enough.
+    pub fn new(text: String) -> Self {
+        lock_region(text.as_ptr(), text.capacity());
+        Self(text)
+    }
+
+    /// Takes over deciphered bytes without copying them; `None` (with the bytes wiped)
+    /// if they are not UTF-8.
+    pub fn from_utf8(mut bytes: Zeroizing<Vec<u8>>) -> Option<Self> {
+        match String::from_utf8(std::mem::take(&mut *bytes)) {
+            Ok(text) => Some(Self::new(text)),
+            Err(e) => {
+                e.into_bytes().zeroize();
+                None
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.0.as_ptr(), self.0.capacity());
+        self.zeroize();
+        unlock_region(ptr, capacity);
+    }
+}
+
+impl ZeroizeOnDrop for SecretString {}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
         &self.0
     }
 }
+
+impl From<String> for SecretString {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(text: &str) -> Self {
+        Self::new(text.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString(***)")
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::crypto::secret::SecretKey;
+use crate::env::parser::{
+    decipher_to_string, encipher_and_verify, ensure_state_dir, is_enciphered, read_env_file,
+    state_file, write_to_env_file,
+};
+use crate::error::{EnvcipherError, Result};
+
+/// Number of backups kept per env file; older ones are pruned.
+pub const MAX_BACKUPS: usize = 10;
+
+const BACKUP_EXTENSION: &str = "enc";
+
+/// An enciphered copy of an env file as it was before a write.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: SystemTime,
+}
+
+/// `<project>/.envcipher/backups/<env file name>/`.
+pub fn backup_dir(env_path: &Path) -> PathBuf {
+    state_file(env_path, "backups")
+}
+
+/// Stores `previous_contents` (enciphering it first if it is plaintext) and prunes the
+/// oldest backups beyond [`MAX_BACKUPS`].
+///
+/// Nothing is stored when it deciphers to the same content as the newest backup, so
+/// repeated unlock/lock cycles do not push real earlier versions out of the ring.
+/// Contents `key` cannot decipher are kept as they are. Returns whether a backup was
+/// written.
+pub fn save_backup(env_path: &Path, key: &SecretKey, previous_contents: &str) -> Result<bool> {
+    if matches_newest_backup(env_path, key, previous_contents)? {
+        return Ok(false);
+    }
+
+    let enciphered = if is_enciphered(previous_contents) {
+        previous_contents.to_string()
+    } else {
+        encipher_and_verify(key, previous_contents)?
+    };
+
+    // Backups are enciphered, but the state dir keeps them out of commits by default.
+    ensure_state_dir(env_path)?;
+    let dir = backup_dir(env_path);
+    fs::create_dir_all(&dir).map_err(EnvcipherError::Io)?;
+
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    // Two writes within the same millisecond must not overwrite each other.
+    while backup_path(&dir, millis).exists() {
+        millis += 1;
+    }
+
+    write_to_env_file(&backup_path(&dir, millis), &enciphered)?;
+
+    for stale in list_backups(env_path)?.into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(&stale.path).map_err(EnvcipherError::Io)?;
+    }
+
+    Ok(true)
+}
+
+fn matches_newest_backup(env_path: &Path, key: &SecretKey, contents: &str) -> Result<bool> {
+    let Some(newest) = list_backups(env_path)?.into_iter().next() else {
+        return Ok(false);
+    };
+
+    // Anything enciphered with another key (or damaged) never counts as a match.
+    let Ok(newest_plaintext) =
+        read_env_file(&newest.path).and_then(|backup| decipher_to_string(key, &backup))
+    else {
+        return Ok(false);
+    };
+    let Ok(plaintext) = decipher_to_string(key, contents) else {
+        return Ok(false);
+    };
+
+    Ok(*plaintext == *newest_plaintext)
+}
+
+/// Records the ciphertext `unlock` is about to replace, so `lock` can back it up
+/// instead of the plaintext it is locking.
+pub fn remember_unlocked_ciphertext(env_path: &Path, ciphertext: &str) -> Result<()> {
+    ensure_state_dir(env_path)?;
+    let path = state_file(env_path, "unlocked");
+    fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).map_err(EnvcipherError::Io)?;
+    write_to_env_file(&path, ciphertext)
+}
+
+/// Returns and forgets the ciphertext recorded by [`remember_unlocked_ciphertext`].
+pub fn take_unlocked_ciphertext(env_path: &Path) -> Option<String> {
+    let path = state_file(env_path, "unlocked");
+    let recorded = read_env_file(&path).ok()?;
+    let _ = fs::remove_file(&path);
+    Some(recorded)
+}
+
+/// Backups for `env_path`, newest first.
+pub fn list_backups(env_path: &Path) -> Result<Vec<Backup>> {
+    let dir = backup_dir(env_path);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(EnvcipherError::Io)? {
+        let path = entry.map_err(EnvcipherError::Io)?.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some(BACKUP_EXTENSION) {
+            continue;
+        }
+
+        let Some(millis) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        backups.push(Backup {
+            path,
+            created: UNIX_EPOCH + Duration::from_millis(millis),
+        });
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    Ok(backups)
+}
+
+fn backup_path(dir: &Path, millis: u128) -> PathBuf {
+    dir.join(format!("{}.{}", millis, BACKUP_EXTENSION))
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// A single variable-level difference between two env documents. Values are wiped
+/// on drop and left out of `Debug`.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub enum VarChange {
+    Added {
+        key: String,
+        value: String,
+    },
+    Removed {
+        key: String,
+        value: String,
+    },
+    Changed {
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl VarChange {
+    pub fn key(&self) -> &str {
+        match self {
+            VarChange::Added { key, .. }
+            | VarChange::Removed { key, .. }
+            | VarChange::Changed { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Debug for VarChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            VarChange::Added { .. } => "Added",
+            VarChange::Removed { .. } => "Removed",
+            VarChange::Changed { .. } => "Changed",
+        };
+        f.debug_struct(kind)
+            .field("key", &self.key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compares variables by key. When a key repeats, the last value wins, matching how
+/// values end up in the environment.
+pub fn diff_env(old: &[(String, String)], new: &[(String, String)]) -> Vec<VarChange> {
+    let old_values = last_values(old);
+    let new_values = last_values(new);
+    let mut changes = Vec::new();
+
+    for key in ordered_keys(old) {
+        let old_value = old_values[key];
+        match new_values.get(key) {
+            None => changes.push(VarChange::Removed {
+                key: key.to_string(),
+                value: old_value.to_string(),
+            }),
+            Some(new_value) if new_value != &old_value => changes.push(VarChange::Changed {
+                key: key.to_string(),
+                old: old_value.to_string(),
+                new: new_value.to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for key in ordered_keys(new) {
+        if !old_values.contains_key(key) {
+            changes.push(VarChange::Added {
+                key: key.to_string(),
+                value: new_values[key].to_string(),
+            });
+        }
+    }
+
+    changes
+}
+
+fn last_values(vars: &[(String, String)]) -> HashMap<&str, &str> {
+    vars.iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect()
+}
+
+/// Keys in first-seen order without duplicates.
+fn ordered_keys(vars: &[(String, String)]) -> Vec<&str> {
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in vars {
+        if !keys.contains(&key.as_str()) {
+            keys.push(key);
+        }
+    }
+    keys
+}
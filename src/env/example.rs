@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use crate::env::parser::{line_key, parse_env_content};
+
+/// Builds a shareable `.env.example` from deciphered env content: comments, blank
+/// lines and key order are kept, values are dropped.
+///
+/// Placeholders already written in `existing` (e.g. `PORT=3000`) are carried over for
+/// keys that still exist. Commented-out assignments lose their values too, since they
+/// are often old secrets.
+pub fn render_example(plaintext: &str, existing: Option<&str>) -> String {
+    let existing_vars = existing.map(parse_env_content).unwrap_or_default();
+    let placeholders: HashMap<&str, &str> = existing_vars
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let mut seen: Vec<&str> = Vec::new();
+    let mut output = String::new();
+
+    for line in plaintext.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            output.push('\n');
+            continue;
+        }
+
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            match line_key(comment).filter(|key| is_key_like(key)) {
+                Some(key) => output.push_str(&format!("# {}=\n", key)),
+                None => {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+            continue;
+        }
+
+        let Some(key) = line_key(line) else {
+            continue;
+        };
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+
+        let placeholder = placeholders.get(key).copied().unwrap_or("");
+        output.push_str(&format!("{}={}\n", key, placeholder));
+    }
+
+    output
+}
+
+fn is_key_like(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
@@ -0,0 +1,53 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::env::parser::{ensure_state_dir, state_file};
+use crate::error::{EnvcipherError, Result};
+
+/// Advisory lock held for the whole read-modify-write of an env file, so concurrent
+/// `edit`, `lock` and `unlock` runs cannot clobber each other. Released on drop.
+///
+/// The lock file lives in the project's `.envcipher/` state dir rather than a shared
+/// temp dir, where another local user could pre-create or hold it.
+#[derive(Debug)]
+pub struct EnvFileLock {
+    _file: File,
+}
+
+impl EnvFileLock {
+    pub fn acquire(env_path: &Path) -> Result<Self> {
+        let lock_path = lock_path_for(env_path)?;
+
+        let file = match OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            // Another user of a shared project created it; a read-only handle locks just as well.
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                File::open(&lock_path).map_err(EnvcipherError::Io)?
+            }
+            Err(e) => return Err(EnvcipherError::Io(e)),
+        };
+
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(EnvcipherError::FileBusy(env_path.to_path_buf())),
+            Err(TryLockError::Error(e)) => Err(EnvcipherError::Io(e)),
+        }
+    }
+}
+
+/// `.envcipher/locks/<file name>`, resolved through symlinks so every path to the same
+/// env file shares one lock.
+fn lock_path_for(env_path: &Path) -> Result<PathBuf> {
+    let env_path = fs::canonicalize(env_path).unwrap_or_else(|_| env_path.to_path_buf());
+
+    ensure_state_dir(&env_path)?;
+    let lock_path = state_file(&env_path, "locks");
+    fs::create_dir_all(lock_path.parent().unwrap_or(Path::new("."))).map_err(EnvcipherError::Io)?;
+    Ok(lock_path)
+}
@@ -0,0 +1,61 @@
+use crate::env::parser::EnvVars;
+
+/// Selects variables by name with `--only`/`--exclude` style glob patterns.
+///
+/// Patterns support `*` (any run of characters) and `?` (one character). With no
+/// `only` patterns every name is included; `exclude` always wins.
+#[derive(Debug, Clone, Default)]
+pub struct VarFilter {
+    pub only: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl VarFilter {
+    pub fn new(only: Vec<String>, exclude: Vec<String>) -> Self {
+        Self { only, exclude }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let included =
+            self.only.is_empty() || self.only.iter().any(|pattern| glob_match(pattern, name));
+
+        included && !self.exclude.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    /// Drops (and wipes) the variables that do not match.
+    pub fn apply(&self, vars: &mut EnvVars) {
+        vars.retain(|name| self.matches(name));
+    }
+}
+
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was and how much of the name it has swallowed so far.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, swallowed)) => {
+                    p = star + 1;
+                    n = swallowed + 1;
+                    backtrack = Some((star, swallowed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
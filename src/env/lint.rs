@@ -0,0 +1,237 @@
+use zeroize::Zeroizing;
+
+use crate::crypto::secret::SecretString;
+
+/// A problem in deciphered env content. Messages never include values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    /// 1-based line number.
+    pub line: usize,
+    pub kind: LintKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// Neither a comment nor `KEY=VALUE`.
+    MissingEquals,
+    InvalidKey(String),
+    LowercaseKey(String),
+    /// Only the last occurrence takes effect.
+    DuplicateKey {
+        key: String,
+        first_line: usize,
+    },
+    UnbalancedQuotes(String),
+    UnquotedSpaces(String),
+    EmptyValue(String),
+    TrailingWhitespace,
+    Bom,
+    CrLf,
+}
+
+impl LintIssue {
+    /// Errors change what the variables end up being; the rest are style warnings.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.kind,
+            LintKind::MissingEquals
+                | LintKind::InvalidKey(_)
+                | LintKind::DuplicateKey { .. }
+                | LintKind::UnbalancedQuotes(_)
+        )
+    }
+
+    /// Whether [`fix_env`] resolves it.
+    pub fn is_fixable(&self) -> bool {
+        matches!(
+            self.kind,
+            LintKind::DuplicateKey { .. }
+                | LintKind::UnquotedSpaces(_)
+                | LintKind::TrailingWhitespace
+                | LintKind::Bom
+                | LintKind::CrLf
+        )
+    }
+
+    pub fn message(&self) -> String {
+        match &self.kind {
+            LintKind::MissingEquals => "expected KEY=VALUE or a # comment".to_string(),
+            LintKind::InvalidKey(key) => format!(
+                "invalid key `{}` (use letters, digits and _, not starting with a digit)",
+                key
+            ),
+            LintKind::LowercaseKey(key) => format!("key `{}` is not uppercase", key),
+            LintKind::DuplicateKey { key, first_line } => format!(
+                "duplicate key `{}` (first on line {}); the last value wins",
+                key, first_line
+            ),
+            LintKind::UnbalancedQuotes(key) => format!("value of `{}` has unbalanced quotes", key),
+            LintKind::UnquotedSpaces(key) => {
+                format!("value of `{}` contains spaces but is not quoted", key)
+            }
+            LintKind::EmptyValue(key) => format!("`{}` is empty", key),
+            LintKind::TrailingWhitespace => "trailing whitespace".to_string(),
+            LintKind::Bom => "file starts with a byte order mark".to_string(),
+            LintKind::CrLf => "CRLF (Windows) line endings".to_string(),
+        }
+    }
+}
+
+pub fn lint_env(content: &str) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    if content.starts_with('\u{feff}') {
+        issues.push(LintIssue {
+            line: 1,
+            kind: LintKind::Bom,
+        });
+    }
+
+    if let Some(index) = content.split('\n').position(|line| line.ends_with('\r')) {
+        issues.push(LintIssue {
+            line: index + 1,
+            kind: LintKind::CrLf,
+        });
+    }
+
+    let mut seen: Vec<(String, usize)> = Vec::new();
+
+    for (index, raw) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+        let mut push = |kind| {
+            issues.push(LintIssue {
+                line: line_number,
+                kind,
+            })
+        };
+
+        if line != line.trim_end() {
+            push(LintKind::TrailingWhitespace);
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once('=') else {
+            push(LintKind::MissingEquals);
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        if !is_valid_key(key) {
+            push(LintKind::InvalidKey(key.to_string()));
+        } else if key.chars().any(|c| c.is_ascii_lowercase()) {
+            push(LintKind::LowercaseKey(key.to_string()));
+        }
+
+        match seen.iter().find(|(seen_key, _)| seen_key == key) {
+            Some((_, first_line)) => push(LintKind::DuplicateKey {
+                key: key.to_string(),
+                first_line: *first_line,
+            }),
+            None => seen.push((key.to_string(), line_number)),
+        }
+
+        if value.is_empty() {
+            push(LintKind::EmptyValue(key.to_string()));
+        } else if !quotes_balanced(value) {
+            push(LintKind::UnbalancedQuotes(key.to_string()));
+        } else if !is_quoted(value) && value.contains(char::is_whitespace) {
+            push(LintKind::UnquotedSpaces(key.to_string()));
+        }
+    }
+
+    issues
+}
+
+/// Fixes what can be fixed mechanically: strips the BOM, CRLF and trailing
+/// whitespace, quotes values containing spaces, and drops all but the last occurrence
+/// of a duplicated key. Everything else is left as is. The result is written straight
+/// into a buffer sized for the worst case, so it never reallocates.
+pub fn fix_env(content: &str) -> SecretString {
+    let lines: Vec<&str> = content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).trim_end())
+        .collect();
+
+    let key_of = |line: &str| -> Option<String> {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            return None;
+        }
+        trimmed
+            .split_once('=')
+            .map(|(key, _)| key.trim().to_string())
+    };
+
+    // Each line grows by at most a pair of quotes and its newline.
+    let mut output = Zeroizing::new(String::with_capacity(content.len() + 3 * lines.len()));
+
+    for (index, line) in lines.iter().enumerate() {
+        let key = key_of(line);
+
+        // Only the last occurrence takes effect, so that is the one to keep.
+        if let Some(key) = &key
+            && lines[index + 1..]
+                .iter()
+                .any(|later| key_of(later).as_ref() == Some(key))
+        {
+            continue;
+        }
+
+        match (key, line.split_once('=')) {
+            (Some(_), Some((key_part, value))) => {
+                let value = value.trim();
+                output.push_str(key_part.trim_end());
+                output.push('=');
+                if !value.is_empty()
+                    && quotes_balanced(value)
+                    && !is_quoted(value)
+                    && value.contains(char::is_whitespace)
+                {
+                    push_quoted(&mut output, value);
+                } else {
+                    output.push_str(value);
+                }
+                output.push('\n');
+            }
+            _ => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+
+    SecretString::new(std::mem::take(&mut *output))
+}
+
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_quoted(value: &str) -> bool {
+    value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+}
+
+/// Only a value that opens with a quote must close with it; quotes further in are
+/// literal characters.
+fn quotes_balanced(value: &str) -> bool {
+    let opens = value.starts_with('"') || value.starts_with('\'');
+    !opens || is_quoted(value)
+}
+
+fn push_quoted(output: &mut String, value: &str) {
+    let quote = if value.contains('"') { '\'' } else { '"' };
+    output.push(quote);
+    output.push_str(value);
+    output.push(quote);
+}
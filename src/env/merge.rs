@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use zeroize::Zeroizing;
+
+use crate::crypto::secret::SecretString;
+use crate::env::diff::{VarChange, diff_env};
+use crate::env::parser::{line_key, parse_env_content};
+
+/// Replays the variable changes made between `base` and `ours` onto `theirs`, keeping
+/// the layout and comments of `theirs`. Keys changed differently on both sides are
+/// returned as conflicts instead. Lines are borrowed from the inputs and joined into
+/// a buffer sized up front, so no unwiped copy of the plaintext is left behind.
+pub fn merge_env(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+) -> std::result::Result<SecretString, Vec<String>> {
+    let base_vars = parse_env_content(base);
+    let their_vars = parse_env_content(theirs);
+    let base_values: HashMap<&str, &str> = base_vars
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let their_values: HashMap<&str, &str> = their_vars
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let mut lines: Vec<&str> = theirs.lines().collect();
+    let mut conflicts = Vec::new();
+
+    for change in diff_env(&base_vars, &parse_env_content(ours)) {
+        let key = change.key();
+        let our_value = match &change {
+            VarChange::Added { value, .. } | VarChange::Changed { new: value, .. } => {
+                Some(value.as_str())
+            }
+            VarChange::Removed { .. } => None,
+        };
+        let their_value = their_values.get(key).copied();
+
+        if their_value == our_value {
+            continue;
+        }
+
+        if their_value != base_values.get(key).copied() {
+            conflicts.push(key.to_string());
+            continue;
+        }
+
+        let positions: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line_key(line) == Some(key))
+            .map(|(index, _)| index)
+            .collect();
+
+        match our_value {
+            Some(_) => {
+                let our_line = ours
+                    .lines()
+                    .rev()
+                    .find(|line| line_key(line) == Some(key))
+                    .map(str::trim)
+                    .unwrap_or_default();
+
+                match positions.first() {
+                    Some(&first) => {
+                        lines[first] = our_line;
+                        for &index in positions[1..].iter().rev() {
+                            lines.remove(index);
+                        }
+                    }
+                    None => lines.push(our_line),
+                }
+            }
+            None => {
+                for &index in positions.iter().rev() {
+                    lines.remove(index);
+                }
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    let length: usize = lines.iter().map(|line| line.len() + 1).sum();
+    let mut merged = Zeroizing::new(String::with_capacity(length));
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            merged.push('\n');
+        }
+        merged.push_str(line);
+    }
+    if !merged.is_empty() {
+        merged.push('\n');
+    }
+    Ok(SecretString::new(std::mem::take(&mut *merged)))
+}
@@ -1 +1,10 @@
+pub mod backup;
+pub mod diff;
+pub mod example;
+pub mod filelock;
+pub mod filter;
+pub mod lint;
+pub mod merge;
 pub mod parser;
+pub mod redact;
+pub mod schema;
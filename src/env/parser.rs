@@ -1,17 +1,31 @@
+use std::fmt;
 use std::fs;
+use std::io::Write;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
 
-use crate::crypto::aead::NONCE_LEN;
+use crate::crypto::aead::{NONCE_LEN, aes_decipher, aes_encipher};
+use crate::crypto::secret::{SecretKey, SecretString};
 use crate::error::{EnvcipherError, Result};
 
+/// Version tag of the enciphered file format.
+pub const FORMAT_VERSION: &str = "v1";
+
 /// Enciphered file format prefix.
 const FORMAT_PREFIX: &str = "ENVCIPHER:v1:";
 
 const ENV_FILENAME: &str = ".env";
 
+/// Owner-only permissions used for plaintext env files on Unix.
+pub const PRIVATE_MODE: u32 = 0o600;
+
+/// Per-project directory for envcipher's own files, next to the env file.
+pub const STATE_DIR: &str = ".envcipher";
+
 /// Stops at project boundary (.git), home dir, or filesystem root.
 pub fn find_env_file(start_dir: &Path) -> Result<PathBuf> {
     let home_dir = dirs::home_dir();
@@ -41,6 +55,66 @@ pub fn find_env_file(start_dir: &Path) -> Result<PathBuf> {
     }
 }
 
+/// `<project>/.envcipher/` for the project owning `env_path`.
+pub fn state_dir(env_path: &Path) -> PathBuf {
+    env_path.parent().unwrap_or(Path::new(".")).join(STATE_DIR)
+}
+
+/// Creates the state dir if needed, with a `.gitignore` keeping its contents out of commits.
+pub fn ensure_state_dir(env_path: &Path) -> Result<PathBuf> {
+    let dir = state_dir(env_path);
+    fs::create_dir_all(&dir).map_err(EnvcipherError::Io)?;
+
+    let ignore_path = dir.join(".gitignore");
+    if !ignore_path.exists() {
+        fs::write(&ignore_path, "*\n").map_err(EnvcipherError::Io)?;
+    }
+
+    Ok(dir)
+}
+
+/// Records the mode a locked file had before `unlock` made it private, so
+/// `lock --keep-mode` can put it back.
+pub fn remember_locked_mode(env_path: &Path, mode: u32) -> Result<()> {
+    ensure_state_dir(env_path)?;
+    let path = state_file(env_path, "modes");
+    fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).map_err(EnvcipherError::Io)?;
+    fs::write(&path, format!("{:o}\n", mode)).map_err(EnvcipherError::Io)
+}
+
+/// Returns and forgets the mode recorded by [`remember_locked_mode`].
+pub fn take_locked_mode(env_path: &Path) -> Option<u32> {
+    let path = state_file(env_path, "modes");
+    let recorded = fs::read_to_string(&path).ok()?;
+    let _ = fs::remove_file(&path);
+    u32::from_str_radix(recorded.trim(), 8).ok()
+}
+
+/// `<state dir>/<kind>/<env file name>`, for per-file state such as backups.
+pub fn state_file(env_path: &Path, kind: &str) -> PathBuf {
+    let file_name = env_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ENV_FILENAME.to_string());
+
+    state_dir(env_path).join(kind).join(file_name)
+}
+
+/// Sets permission bits on Unix; a no-op elsewhere.
+pub fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(EnvcipherError::Io)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
 pub fn hash_directory_path(path: &Path) -> String {
     let path_str = path.to_string_lossy();
     let hash = Sha256::digest(path_str.as_bytes());
@@ -51,8 +125,118 @@ pub fn read_env_file(path: &Path) -> Result<String> {
     fs::read_to_string(path).map_err(EnvcipherError::Io)
 }
 
+/// Writes through a synced temp file in the same directory that is renamed over `path`,
+/// so a crash or full disk leaves either the old or the new file, never a truncated one.
+/// The existing file's permissions and ownership are kept.
 pub fn write_to_env_file(path: &Path, contents: &str) -> Result<()> {
-    fs::write(path, contents).map_err(EnvcipherError::Io)
+    write_atomically(path, contents, None)
+}
+
+/// Like [`write_to_env_file`], but a new file gets the usual umask-derived permissions
+/// instead of the temp file's 0600. For files meant to be shared, like `.env.example`.
+pub fn write_shared_file(path: &Path, contents: &str) -> Result<()> {
+    // Creating the file first lets the OS apply the umask; the atomic write keeps it.
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(EnvcipherError::Io(e)),
+    }
+
+    write_atomically(path, contents, None)
+}
+
+/// Like [`write_to_env_file`], but restricts the file to its owner (0600) on Unix since
+/// it holds plaintext secrets.
+pub fn write_plaintext_env_file(path: &Path, contents: &str) -> Result<()> {
+    write_atomically(path, contents, Some(PRIVATE_MODE))
+}
+
+/// Permission bits of `path`, or `None` where modes do not apply.
+pub fn file_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions().mode() & 0o777)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Whether `mode` lets the group or others read the file.
+pub fn is_readable_by_others(mode: u32) -> bool {
+    mode & 0o044 != 0
+}
+
+fn write_atomically(path: &Path, contents: &str, mode: Option<u32>) -> Result<()> {
+    // Write through symlinks instead of replacing them with a regular file.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".envcipher-").suffix(".tmp");
+
+    // A fixed mode is set when the temp file is created, so the plaintext is never
+    // readable under the old file's permissions, even briefly.
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(mode));
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut temp_file = builder.tempfile_in(dir).map_err(EnvcipherError::Io)?;
+
+    if let Ok(metadata) = fs::metadata(&path) {
+        if mode.is_none() {
+            temp_file
+                .as_file()
+                .set_permissions(metadata.permissions())
+                .map_err(EnvcipherError::Io)?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            // Only root can give files away; for everyone else ownership already matches.
+            let _ = std::os::unix::fs::fchown(
+                temp_file.as_file(),
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+            );
+        }
+    }
+
+    temp_file
+        .write_all(contents.as_bytes())
+        .map_err(EnvcipherError::Io)?;
+
+    temp_file.as_file().sync_all().map_err(EnvcipherError::Io)?;
+    temp_file
+        .persist(&path)
+        .map_err(|e| EnvcipherError::Io(e.error))?;
+
+    // Persist the rename itself.
+    #[cfg(unix)]
+    if let Ok(dir_handle) = fs::File::open(dir) {
+        let _ = dir_handle.sync_all();
+    }
+
+    Ok(())
 }
 
 /// Returns true only if:
@@ -101,6 +285,37 @@ pub fn format_enciphered_text(nonce: &[u8; NONCE_LEN], ciphertext: &[u8]) -> Str
     format!("{}{nonce_b64}:{ciphertext_b64}\n", FORMAT_PREFIX)
 }
 
+/// Enciphers `plaintext` into file format and checks that the result deciphers back to it
+/// before anything is written to disk.
+pub fn encipher_and_verify(key: &SecretKey, plaintext: &str) -> Result<String> {
+    let (ciphertext, nonce) = aes_encipher(key, plaintext.as_bytes())?;
+    let enciphered_text = format_enciphered_text(&nonce, &ciphertext);
+
+    let (parsed_nonce, parsed_ciphertext) = parse_enciphered_file(&enciphered_text)?;
+    let round_trip = aes_decipher(key, &parsed_nonce, &parsed_ciphertext)?;
+
+    if round_trip.as_slice() != plaintext.as_bytes() {
+        return Err(EnvcipherError::Encipherment(
+            "verification failed: ciphertext does not decipher to the original".to_string(),
+        ));
+    }
+
+    Ok(enciphered_text)
+}
+
+/// Deciphers enciphered `contents`; plaintext contents are returned unchanged.
+pub fn decipher_to_string(key: &SecretKey, contents: &str) -> Result<SecretString> {
+    if !is_enciphered(contents) {
+        return Ok(SecretString::from(contents));
+    }
+
+    let (nonce, ciphertext) = parse_enciphered_file(contents)?;
+    let plaintext_bytes = aes_decipher(key, &nonce, &ciphertext)?;
+    SecretString::from_utf8(plaintext_bytes).ok_or_else(|| {
+        EnvcipherError::Decipherment("deciphered content is not valid UTF-8".to_string())
+    })
+}
+
 pub fn parse_enciphered_file(contents: &str) -> Result<([u8; NONCE_LEN], Vec<u8>)> {
     let contents = contents.trim();
 
@@ -141,8 +356,77 @@ pub fn parse_enciphered_file(contents: &str) -> Result<([u8; NONCE_LEN], Vec<u8>
     Ok((nonce, ciphertext))
 }
 
-pub fn parse_env_content(content: &str) -> Vec<(String, String)> {
-    let mut vars = Vec::new();
+/// Variable name assigned by `line`, or `None` for blanks, comments and other lines.
+pub fn line_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    line.split_once('=').map(|(key, _)| key.trim())
+}
+
+/// Parsed `KEY=VALUE` pairs in file order. Values are wiped on drop and left out of
+/// `Debug`.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct EnvVars(Vec<(String, String)>);
+
+impl EnvVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, key: String, value: String) {
+        self.0.push((key, value));
+    }
+
+    /// Keeps the variables whose key matches; the values of the others are wiped.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.0.retain_mut(|(key, value)| {
+            let kept = keep(key);
+            if !kept {
+                value.zeroize();
+            }
+            kept
+        });
+    }
+}
+
+impl Deref for EnvVars {
+    type Target = [(String, String)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a EnvVars {
+    type Item = &'a (String, String);
+    type IntoIter = std::slice::Iter<'a, (String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Drop for EnvVars {
+    fn drop(&mut self) {
+        for (_, value) in &mut self.0 {
+            value.zeroize();
+        }
+    }
+}
+
+impl fmt::Debug for EnvVars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(key, _)| (key, "***")))
+            .finish()
+    }
+}
+
+pub fn parse_env_content(content: &str) -> EnvVars {
+    let mut vars = EnvVars::new();
 
     for line in content.lines() {
         let line = line.trim();
@@ -161,7 +445,7 @@ pub fn parse_env_content(content: &str) -> Vec<(String, String)> {
                 value = &value[1..value.len() - 1];
             }
 
-            vars.push((key, value.to_string()));
+            vars.push(key, value.to_string());
         }
     }
 
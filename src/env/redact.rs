@@ -0,0 +1,98 @@
+use zeroize::Zeroize;
+
+/// Values shorter than this are left alone; masking `true` or `3000` everywhere
+/// would make output unreadable without protecting anything.
+pub const MIN_REDACT_LEN: usize = 6;
+
+/// Streaming replacement of secret values with `***KEY_NAME***`.
+///
+/// Output is fed in arbitrary chunks. Bytes are held back only while they could still
+/// be the start of a secret, so prompts without a trailing newline still show up.
+pub struct Redactor {
+    /// (value, replacement), longest value first so overlapping secrets mask fully.
+    secrets: Vec<(Vec<u8>, Vec<u8>)>,
+    pending: Vec<u8>,
+}
+
+impl Redactor {
+    pub fn new(vars: &[(String, String)]) -> Self {
+        let mut secrets: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+
+        for (key, value) in vars {
+            if value.len() < MIN_REDACT_LEN
+                || secrets
+                    .iter()
+                    .any(|(existing, _)| existing == value.as_bytes())
+            {
+                continue;
+            }
+            secrets.push((
+                value.as_bytes().to_vec(),
+                format!("***{}***", key).into_bytes(),
+            ));
+        }
+
+        secrets.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+
+        Self {
+            secrets,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Whether no value qualified for redaction, so output can pass through untouched.
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Returns the redacted bytes that are safe to emit so far.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut output = Vec::with_capacity(self.pending.len());
+        let mut i = 0;
+
+        while i < self.pending.len() {
+            let rest = &self.pending[i..];
+
+            if let Some((value, replacement)) = self
+                .secrets
+                .iter()
+                .find(|(value, _)| rest.starts_with(value))
+            {
+                output.extend_from_slice(replacement);
+                i += value.len();
+                continue;
+            }
+
+            // Could still turn into a secret once more output arrives.
+            if self
+                .secrets
+                .iter()
+                .any(|(value, _)| value.len() > rest.len() && value.starts_with(rest))
+            {
+                break;
+            }
+
+            output.push(self.pending[i]);
+            i += 1;
+        }
+
+        self.pending.drain(..i);
+        output
+    }
+
+    /// Flushes whatever was held back once the stream has ended.
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+impl Drop for Redactor {
+    fn drop(&mut self) {
+        for (value, _) in &mut self.secrets {
+            value.zeroize();
+        }
+        self.pending.zeroize();
+    }
+}
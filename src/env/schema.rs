@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::{EnvcipherError, Result};
+
+/// Explicit schema, committed next to the env file.
+pub const SCHEMA_FILENAME: &str = ".env.schema";
+
+/// Used as a schema (every key required, any value) when there is no `.env.schema`.
+pub const EXAMPLE_FILENAME: &str = ".env.example";
+
+/// What a variable's value must look like.
+#[derive(Debug, Clone)]
+pub enum VarType {
+    String,
+    Int,
+    Bool,
+    Url,
+    Email,
+    /// `pattern` is what the schema says; `regex` anchors it to the whole value.
+    Regex {
+        pattern: String,
+        regex: Regex,
+    },
+}
+
+impl VarType {
+    fn parse(spec: &str) -> std::result::Result<Self, String> {
+        match spec {
+            "string" => Ok(VarType::String),
+            "int" => Ok(VarType::Int),
+            "bool" => Ok(VarType::Bool),
+            "url" => Ok(VarType::Url),
+            "email" => Ok(VarType::Email),
+            other => match other.strip_prefix("regex:") {
+                Some(pattern) => Regex::new(&format!("^(?:{})$", pattern))
+                    .map(|regex| VarType::Regex {
+                        pattern: pattern.to_string(),
+                        regex,
+                    })
+                    .map_err(|e| format!("invalid regex: {}", e)),
+                None => Err(format!(
+                    "unknown type `{}` (expected string, int, bool, url, email or regex:<pattern>)",
+                    other
+                )),
+            },
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            VarType::String => "string".to_string(),
+            VarType::Int => "int".to_string(),
+            VarType::Bool => "bool".to_string(),
+            VarType::Url => "url".to_string(),
+            VarType::Email => "email".to_string(),
+            VarType::Regex { pattern, .. } => format!("regex:{}", pattern),
+        }
+    }
+
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            VarType::String => true,
+            VarType::Int => value.parse::<i64>().is_ok(),
+            VarType::Bool => matches!(
+                value.to_ascii_lowercase().as_str(),
+                "true" | "false" | "1" | "0" | "yes" | "no" | "on" | "off"
+            ),
+            VarType::Url => value.split_once("://").is_some_and(|(scheme, rest)| {
+                !scheme.is_empty()
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                    && !rest.is_empty()
+                    && !rest.contains(char::is_whitespace)
+            }),
+            VarType::Email => value.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !value.contains(char::is_whitespace)
+            }),
+            VarType::Regex { regex, .. } => regex.is_match(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VarSpec {
+    pub key: String,
+    pub var_type: VarType,
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+/// A problem found by [`Schema::validate`]. Values are never included, since they
+/// are usually secrets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaIssue {
+    Missing {
+        key: String,
+        description: Option<String>,
+    },
+    Malformed {
+        key: String,
+        expected: String,
+        description: Option<String>,
+    },
+    Unexpected {
+        key: String,
+    },
+}
+
+impl SchemaIssue {
+    pub fn key(&self) -> &str {
+        match self {
+            SchemaIssue::Missing { key, .. }
+            | SchemaIssue::Malformed { key, .. }
+            | SchemaIssue::Unexpected { key } => key,
+        }
+    }
+
+    /// Unexpected variables are reported but do not fail validation.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, SchemaIssue::Unexpected { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub path: PathBuf,
+    pub vars: Vec<VarSpec>,
+}
+
+impl Schema {
+    /// `.env.schema`, else `.env.example`, next to the env file.
+    pub fn find(env_path: &Path) -> Result<Option<Self>> {
+        let dir = env_path.parent().unwrap_or(Path::new("."));
+
+        let schema_path = dir.join(SCHEMA_FILENAME);
+        if schema_path.is_file() {
+            return Self::load(&schema_path).map(Some);
+        }
+
+        let example_path = dir.join(EXAMPLE_FILENAME);
+        if example_path.is_file() {
+            return Self::load(&example_path).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(EnvcipherError::Io)?;
+        let is_example = path.file_name().and_then(|name| name.to_str()) == Some(EXAMPLE_FILENAME);
+
+        let vars = if is_example {
+            parse_example(&content)
+        } else {
+            parse_schema(&content).map_err(|(line, message)| {
+                EnvcipherError::InvalidFormat(format!("{}:{}: {}", path.display(), line, message))
+            })?
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            vars,
+        })
+    }
+
+    /// The last value of a repeated key is the one checked, as it is the one used.
+    pub fn validate(&self, vars: &[(String, String)]) -> Vec<SchemaIssue> {
+        let values: HashMap<&str, &str> = vars
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let mut issues = Vec::new();
+
+        for spec in &self.vars {
+            match values.get(spec.key.as_str()) {
+                None | Some(&"") if spec.required => issues.push(SchemaIssue::Missing {
+                    key: spec.key.clone(),
+                    description: spec.description.clone(),
+                }),
+                Some(value) if !value.is_empty() && !spec.var_type.accepts(value) => {
+                    issues.push(SchemaIssue::Malformed {
+                        key: spec.key.clone(),
+                        expected: spec.var_type.name(),
+                        description: spec.description.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let mut reported = Vec::new();
+        for (key, _) in vars {
+            if !self.vars.iter().any(|spec| &spec.key == key) && !reported.contains(&key) {
+                reported.push(key);
+                issues.push(SchemaIssue::Unexpected { key: key.clone() });
+            }
+        }
+
+        issues
+    }
+}
+
+/// `.env.schema` lines are `KEY=<type> [optional]`; comment lines directly above a key
+/// describe it:
+///
+/// ```text
+/// # Port the server listens on
+/// PORT=int optional
+/// LOG_FORMAT=regex:json|text
+/// ```
+///
+/// A `regex:` pattern runs to the end of the line, so it may contain spaces; flags
+/// go before it (`MODE=optional regex:dev|prod`). A pattern ending in a flag is
+/// rejected rather than silently folding the flag into the pattern.
+fn parse_schema(content: &str) -> std::result::Result<Vec<VarSpec>, (usize, String)> {
+    let mut vars = Vec::new();
+    let mut description: Vec<&str> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            description.clear();
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            description.push(comment.trim());
+            continue;
+        }
+
+        let (key, spec) = line
+            .split_once('=')
+            .ok_or_else(|| (line_number, "expected KEY=<type> [optional]".to_string()))?;
+
+        let (flags, pattern) = match spec.split_once("regex:") {
+            Some((flags, pattern)) => (flags, Some(pattern.trim())),
+            None => (spec, None),
+        };
+
+        if let Some(flag) = pattern
+            .and_then(|pattern| pattern.rsplit_once(char::is_whitespace))
+            .map(|(_, last)| last)
+            .filter(|last| matches!(*last, "optional" | "required"))
+        {
+            return Err((
+                line_number,
+                format!(
+                    "`{}` after a regex would be part of the pattern; put it before `regex:`",
+                    flag
+                ),
+            ));
+        }
+
+        let mut type_spec = pattern.map(|pattern| format!("regex:{}", pattern));
+        let mut required = true;
+
+        for token in flags.split_whitespace() {
+            match token {
+                "optional" => required = false,
+                "required" => required = true,
+                other if type_spec.is_none() => type_spec = Some(other.to_string()),
+                other => return Err((line_number, format!("unexpected `{}`", other))),
+            }
+        }
+
+        let var_type = match type_spec {
+            Some(spec) => VarType::parse(&spec).map_err(|message| (line_number, message))?,
+            None => VarType::String,
+        };
+
+        vars.push(VarSpec {
+            key: key.trim().to_string(),
+            var_type,
+            required,
+            description: (!description.is_empty()).then(|| description.join(" ")),
+        });
+        description.clear();
+    }
+
+    Ok(vars)
+}
+
+/// Every key in `.env.example` is required; comments directly above describe it.
+fn parse_example(content: &str) -> Vec<VarSpec> {
+    let mut vars = Vec::new();
+    let mut description: Vec<&str> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            description.clear();
+        } else if let Some(comment) = line.strip_prefix('#') {
+            description.push(comment.trim());
+        } else if let Some((key, _)) = line.split_once('=') {
+            vars.push(VarSpec {
+                key: key.trim().to_string(),
+                var_type: VarType::String,
+                required: true,
+                description: (!description.is_empty()).then(|| description.join(" ")),
+            });
+            description.clear();
+        }
+    }
+
+    vars
+}
@@ -6,6 +6,31 @@ pub enum EnvcipherError {
     #[error("Keychain access failed: {0}")]
     KeychainAccess(String),
 
+    #[error(
+        "No key found for this project (key ID {0}). Run `envcipher init`, or `envcipher import-key <KEY>` with a key from `envcipher export-key`"
+    )]
+    KeyNotFound(String),
+
+    #[error(
+        "Keychain unavailable: {0}. Make sure the OS keychain is running and unlocked, or run `envcipher doctor`"
+    )]
+    KeychainUnavailable(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Stored key is malformed: {0}. Re-import it with `envcipher import-key <KEY>`")]
+    MalformedKey(String),
+
+    #[error("No .env.schema or .env.example found in {0}")]
+    SchemaNotFound(PathBuf),
+
+    #[error("{0} is out of date. Run `envcipher example` to update it")]
+    ExampleOutdated(PathBuf),
+
+    #[error("Agent error: {0}")]
+    Agent(String),
+
     #[error("Encipherment failed: {0}")]
     Encipherment(String),
 
@@ -15,6 +40,12 @@ pub enum EnvcipherError {
     #[error("No .env file found in {0} or parent directory")]
     EnvNotFound(PathBuf),
 
+    #[error("Env file not found: {0}")]
+    EnvFileNotFound(PathBuf),
+
+    #[error("{0} is being modified by another envcipher process")]
+    FileBusy(PathBuf),
+
     #[error(".env file is already enciphered")]
     AlreadyEnciphered,
 
@@ -36,6 +67,29 @@ pub enum EnvcipherError {
 
     #[error("Invalid enciphered format: {0}")]
     InvalidFormat(String),
+
+    #[error("No backup #{0}. Run `envcipher history` to list backups")]
+    BackupNotFound(usize),
+
+    #[error("{0} check(s) failed")]
+    ChecksFailed(usize),
+
+    #[error("{failed} of {total} projects failed")]
+    ProjectsFailed { failed: usize, total: usize },
+
+    /// The command already reported its result; the process should exit with this code.
+    #[error("exited with code {0}")]
+    Exit(i32),
+}
+
+impl EnvcipherError {
+    /// Process exit status for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EnvcipherError::Exit(code) => *code,
+            _ => 1,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, EnvcipherError>;
@@ -0,0 +1,334 @@
+//! A small `ssh-agent`-style daemon that caches project keys so repeated commands do
+//! not hit the OS keychain (and its unlock prompts) every time.
+//!
+//! The agent listens on a Unix socket in a directory only the current user can enter,
+//! and additionally refuses connections from other users. Cached keys live in locked
+//! (never swapped) memory, are zeroized when dropped, and expire after a TTL.
+//!
+//! The protocol is one line per connection: `GET <id>`, `PUT <id> <base64 key>`,
+//! `FORGET <id>`, `STATUS`, `PING` or `STOP`.
+
+use std::collections::HashMap;
+use std::fs::{self, DirBuilder};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use zeroize::Zeroizing;
+
+use crate::crypto::aead::KEY_LEN;
+use crate::crypto::secret::SecretKey;
+use crate::error::{EnvcipherError, Result};
+use crate::keystore::decode_key;
+
+/// Overrides the socket location, like `SSH_AUTH_SOCK`.
+pub const SOCKET_ENV: &str = "ENVCIPHER_AGENT_SOCK";
+
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_REQUEST_LEN: u64 = 512;
+
+/// What a running agent reports about itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentStatus {
+    pub cached_keys: usize,
+    pub ttl: Duration,
+}
+
+/// `$ENVCIPHER_AGENT_SOCK`, else `envcipher-agent-<uid>/agent.sock` in the runtime
+/// (or temp) directory.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("envcipher-agent-{}", current_uid()))
+        .join("agent.sock")
+}
+
+/// A cached key, or `None` when no agent is running or it does not hold the key.
+pub fn fetch(directory_hash: &str) -> Option<SecretKey> {
+    let reply = request(&format!("GET {}", directory_hash)).ok()?;
+    decode_key(reply.strip_prefix("OK ")?).ok()
+}
+
+/// Hands a key read from the keychain to the agent, if one is running.
+pub fn offer(directory_hash: &str, key: &SecretKey) {
+    let line = Zeroizing::new(format!("PUT {} {}", directory_hash, *key.to_base64()));
+    let _ = request(&line);
+}
+
+/// Drops a key from the agent, if one is running.
+pub fn forget(directory_hash: &str) {
+    let _ = request(&format!("FORGET {}", directory_hash));
+}
+
+/// `None` when no agent answers on the socket.
+pub fn status() -> Option<AgentStatus> {
+    let reply = request("STATUS").ok()?;
+    let mut fields = reply.strip_prefix("OK ")?.split(' ');
+
+    Some(AgentStatus {
+        cached_keys: fields.next()?.parse().ok()?,
+        ttl: Duration::from_secs(fields.next()?.parse().ok()?),
+    })
+}
+
+/// Asks a running agent to wipe its keys and exit. Returns `false` if none was running.
+pub fn stop() -> bool {
+    request("STOP").is_ok()
+}
+
+/// Runs the agent in the current process until `stop` is requested or a termination
+/// signal arrives.
+pub fn serve(ttl: Duration) -> Result<()> {
+    let path = socket_path();
+    prepare_socket_dir(&path)?;
+
+    if path.exists() {
+        if request("PING").is_ok() {
+            return Err(EnvcipherError::Agent(format!(
+                "already running at {}",
+                path.display()
+            )));
+        }
+        // Left behind by an agent that did not shut down cleanly.
+        fs::remove_file(&path).map_err(EnvcipherError::Io)?;
+    }
+
+    let listener = UnixListener::bind(&path).map_err(EnvcipherError::Io)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).map_err(EnvcipherError::Io)?;
+    listener.set_nonblocking(true).map_err(EnvcipherError::Io)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [
+        signal_hook::consts::SIGHUP,
+        signal_hook::consts::SIGINT,
+        signal_hook::consts::SIGTERM,
+    ] {
+        signal_hook::flag::register(signal, Arc::clone(&stop)).map_err(EnvcipherError::Io)?;
+    }
+
+    let cache: Arc<Mutex<HashMap<String, CachedKey>>> = Arc::default();
+
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        lock_cache(&cache).retain(|_, cached| cached.expires > now);
+
+        match listener.accept() {
+            // Each client gets its own thread, so one that never sends a request cannot
+            // hold up everyone else until it times out.
+            Ok((stream, _)) => {
+                let cache = Arc::clone(&cache);
+                let stop = Arc::clone(&stop);
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream, &cache, &stop, ttl) {
+                        eprintln!("envcipher agent: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(EnvcipherError::Io(e)),
+        }
+    }
+
+    lock_cache(&cache).clear();
+    let _ = fs::remove_file(&path);
+
+    Ok(())
+}
+
+fn lock_cache(
+    cache: &Mutex<HashMap<String, CachedKey>>,
+) -> MutexGuard<'_, HashMap<String, CachedKey>> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Answers one request, setting `stop` once the reply to `STOP` is sent.
+fn handle(
+    stream: UnixStream,
+    cache: &Mutex<HashMap<String, CachedKey>>,
+    stop: &AtomicBool,
+    ttl: Duration,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    if peer_uid(&stream)? != current_uid() {
+        return Ok(());
+    }
+
+    let mut line = Zeroizing::new(String::with_capacity(MAX_REQUEST_LEN as usize));
+    BufReader::new((&stream).take(MAX_REQUEST_LEN)).read_line(&mut line)?;
+
+    let mut stopping = false;
+    let mut parts = line.trim_end().splitn(3, ' ');
+
+    let reply = match (parts.next(), parts.next(), parts.next()) {
+        (Some("PING"), None, None) => Zeroizing::new("OK".to_string()),
+        (Some("GET"), Some(id), None) => match lock_cache(cache).get(id) {
+            Some(cached) => Zeroizing::new(format!("OK {}", *cached.key.to_base64())),
+            None => Zeroizing::new("MISS".to_string()),
+        },
+        (Some("PUT"), Some(id), Some(encoded)) => match decode_key(encoded) {
+            Ok(key) => {
+                lock_cache(cache).insert(id.to_string(), CachedKey::new(&key, ttl));
+                Zeroizing::new("OK".to_string())
+            }
+            Err(_) => Zeroizing::new("ERR malformed key".to_string()),
+        },
+        (Some("FORGET"), Some(id), None) => {
+            lock_cache(cache).remove(id);
+            Zeroizing::new("OK".to_string())
+        }
+        (Some("STATUS"), None, None) => {
+            Zeroizing::new(format!("OK {} {}", lock_cache(cache).len(), ttl.as_secs()))
+        }
+        (Some("STOP"), None, None) => {
+            stopping = true;
+            Zeroizing::new("OK".to_string())
+        }
+        _ => Zeroizing::new("ERR unknown request".to_string()),
+    };
+
+    let written = (&stream)
+        .write_all(reply.as_bytes())
+        .and_then(|()| (&stream).write_all(b"\n"));
+
+    if stopping {
+        stop.store(true, Ordering::Relaxed);
+    }
+    written
+}
+
+/// Sends one request line and returns the reply line.
+fn request(line: &str) -> io::Result<Zeroizing<String>> {
+    let path = socket_path();
+    check_socket(&path)?;
+
+    let stream = UnixStream::connect(&path)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    (&stream).write_all(line.as_bytes())?;
+    (&stream).write_all(b"\n")?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = Zeroizing::new(String::with_capacity(MAX_REQUEST_LEN as usize));
+    BufReader::new((&stream).take(MAX_REQUEST_LEN)).read_line(&mut reply)?;
+
+    let trimmed = reply.trim_end().len();
+    reply.truncate(trimmed);
+
+    if reply.starts_with("ERR") {
+        return Err(io::Error::other(reply.to_string()));
+    }
+    Ok(reply)
+}
+
+/// Only talk to a socket owned by the current user.
+fn check_socket(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    if !metadata.file_type().is_socket() || metadata.uid() != current_uid() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not an agent socket owned by you", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn prepare_socket_dir(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(EnvcipherError::Io)?;
+
+    let metadata = fs::metadata(dir).map_err(EnvcipherError::Io)?;
+    if metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(EnvcipherError::Agent(format!(
+            "socket directory {} must be owned by you and private (chmod 700)",
+            dir.display()
+        )));
+    }
+
+    Ok(())
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: `cred` and `len` are valid for writes and sized for SO_PEERCRED.
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut uid = 0;
+    let mut gid = 0;
+
+    // SAFETY: `uid` and `gid` are valid for writes.
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// A key held until `expires`. `SecretKey` keeps it locked in RAM and wipes it on drop.
+struct CachedKey {
+    key: SecretKey,
+    expires: Instant,
+}
+
+impl CachedKey {
+    fn new(key: &SecretKey, ttl: Duration) -> Self {
+        let mut copy = SecretKey::new([0u8; KEY_LEN]);
+        copy.as_mut_bytes().copy_from_slice(key.as_bytes());
+
+        Self {
+            key: copy,
+            expires: Instant::now() + ttl,
+        }
+    }
+}
@@ -1,28 +1,68 @@
 use keyring::Entry;
+use zeroize::Zeroizing;
 
-use crate::crypto::aead::KEY_LEN;
 use crate::crypto::secret::SecretKey;
 use crate::error::{EnvcipherError, Result};
 
 // /// Service name used for credential store entries.
 const SERVICE_NAME: &str = "envcipher";
 
+/// Key-caching agent (Unix only).
+#[cfg(unix)]
+pub mod agent;
+
+/// Asks the agent first, so a cached key counts without a keychain prompt, then the
+/// credential store.
 pub fn key_exists(directory_hash: &str) -> Result<bool> {
+    if key_cached(directory_hash) {
+        return Ok(true);
+    }
+
+    key_stored(directory_hash)
+}
+
+/// Whether the credential store itself holds the key, without asking the agent.
+pub fn key_stored(directory_hash: &str) -> Result<bool> {
     let entry = create_keyring_entry(directory_hash)?;
 
-    match entry.get_password() {
+    match entry.get_password().map(Zeroizing::new) {
         Ok(_) => Ok(true),
         Err(keyring::Error::NoEntry) => Ok(false),
-        Err(e) => Err(EnvcipherError::KeychainAccess(format!(
-            "failed to check key: {}",
-            e
-        ))),
+        Err(e) => Err(classify_error(directory_hash, e, "failed to check key")),
+    }
+}
+
+/// Whether a running agent holds the key. Always `false` where there is no agent.
+pub fn key_cached(directory_hash: &str) -> bool {
+    #[cfg(unix)]
+    return agent::fetch(directory_hash).is_some();
+
+    #[cfg(not(unix))]
+    {
+        let _ = directory_hash;
+        false
     }
 }
 
 fn create_keyring_entry(directory_hash: &str) -> Result<Entry> {
     Entry::new(SERVICE_NAME, directory_hash)
-        .map_err(|e| EnvcipherError::KeychainAccess(format!("failed to create entry: {}", e)))
+        .map_err(|e| classify_error(directory_hash, e, "failed to create entry"))
+}
+
+/// Separates a missing key from an unreachable or locked keychain and from corrupted entries.
+fn classify_error(directory_hash: &str, error: keyring::Error, context: &str) -> EnvcipherError {
+    match error {
+        keyring::Error::NoEntry => EnvcipherError::KeyNotFound(key_id(directory_hash).to_string()),
+        keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_) => {
+            EnvcipherError::KeychainUnavailable(error.to_string())
+        }
+        keyring::Error::BadEncoding(_) => EnvcipherError::MalformedKey(error.to_string()),
+        e => EnvcipherError::KeychainAccess(format!("{}: {}", context, e)),
+    }
+}
+
+fn key_id(directory_hash: &str) -> &str {
+    directory_hash.get(..8).unwrap_or(directory_hash)
 }
 
 /// Overwrites existing key if present.
@@ -30,46 +70,63 @@ pub fn store_key(directory_hash: &str, key: &SecretKey) -> Result<()> {
     let entry = create_keyring_entry(directory_hash)?;
 
     // Store as base64 to avoid binary encoding issues.
-    let key_b64 =
-        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, key.as_bytes());
+    let key_b64 = key.to_base64();
 
     entry
         .set_password(&key_b64)
-        .map_err(|e| EnvcipherError::KeychainAccess(format!("failed to store key: {}", e)))
+        .map_err(|e| classify_error(directory_hash, e, "failed to store key"))?;
+
+    // A running agent must not keep serving the key this one replaces.
+    #[cfg(unix)]
+    agent::forget(directory_hash);
+
+    Ok(())
 }
 
+/// Asks the agent first when one is running, then the credential store. Keys read
+/// from the store are handed to the agent so the next lookup skips the keychain.
 pub fn retrieve_key_from_store(directory_hash: &str) -> Result<SecretKey> {
-    let entry = create_keyring_entry(directory_hash)?;
+    #[cfg(unix)]
+    if let Some(key) = agent::fetch(directory_hash) {
+        return Ok(key);
+    }
 
-    let key_b64 = entry
-        .get_password()
-        .map_err(|e| EnvcipherError::KeychainAccess(format!("failed to retrieve key: {}", e)))?;
+    let key = retrieve_key_from_keychain(directory_hash)?;
 
-    let key_bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &key_b64)
-        .map_err(|e| EnvcipherError::KeychainAccess(format!("invalid key format: {}", e)))?;
+    #[cfg(unix)]
+    agent::offer(directory_hash, &key);
 
-    if key_bytes.len() != KEY_LEN {
-        return Err(EnvcipherError::KeychainAccess(format!(
-            "key has wrong length: expected {}, got {}",
-            KEY_LEN,
-            key_bytes.len()
-        )));
-    }
+    Ok(key)
+}
 
-    let mut key = [0u8; KEY_LEN];
-    key.copy_from_slice(&key_bytes);
-    Ok(SecretKey::new(key))
+fn retrieve_key_from_keychain(directory_hash: &str) -> Result<SecretKey> {
+    let entry = create_keyring_entry(directory_hash)?;
+
+    let key_b64 = Zeroizing::new(
+        entry
+            .get_password()
+            .map_err(|e| classify_error(directory_hash, e, "failed to retrieve key"))?,
+    );
+
+    decode_key(&key_b64)
+}
+
+pub(crate) fn decode_key(key_b64: &str) -> Result<SecretKey> {
+    SecretKey::from_base64(key_b64).map_err(|e| match e {
+        EnvcipherError::InvalidKey(reason) => EnvcipherError::MalformedKey(reason),
+        e => e,
+    })
 }
 
 pub fn delete_key(directory_hash: &str) -> Result<()> {
     let entry = create_keyring_entry(directory_hash)?;
 
+    #[cfg(unix)]
+    agent::forget(directory_hash);
+
     match entry.delete_credential() {
         Ok(_) => Ok(()),
         Err(keyring::Error::NoEntry) => Ok(()), // Not an error if key doesn't exist
-        Err(e) => Err(EnvcipherError::KeychainAccess(format!(
-            "failed to delete key: {}",
-            e
-        ))),
+        Err(e) => Err(classify_error(directory_hash, e, "failed to delete key")),
     }
 }
@@ -64,17 +64,20 @@ fn main() -> PyResult<()> {
     match crate::cli::execute(args) {
         Ok(_) => Ok(()),
         Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+            if !matches!(e, crate::error::EnvcipherError::Exit(_)) {
+                eprintln!("Error: {}", e);
+            }
+            std::process::exit(e.exit_code());
         }
     }
 }
 
 #[cfg(feature = "python")]
 #[pyfunction]
-#[pyo3(signature = (path=None))]
-fn load(py: Python, path: Option<String>) -> PyResult<()> {
+#[pyo3(signature = (path=None, check_schema=false))]
+fn load(py: Python, path: Option<String>, check_schema: bool) -> PyResult<()> {
     use crate::crypto::aead::aes_decipher;
+    use crate::crypto::secret::SecretString;
     use crate::env::parser::{
         find_env_file, is_enciphered, parse_enciphered_file, parse_env_content, read_env_file,
     };
@@ -107,16 +110,54 @@ fn load(py: Python, path: Option<String>) -> PyResult<()> {
             PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Decryption failed: {}", e))
         })?;
 
-        String::from_utf8(decrypted_bytes)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyUnicodeDecodeError, _>(e.to_string()))?
+        SecretString::from_utf8(decrypted_bytes).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyUnicodeDecodeError, _>(
+                "deciphered content is not valid UTF-8",
+            )
+        })?
     } else {
-        content
+        SecretString::new(content)
     };
 
+    let vars = parse_env_content(&plaintext);
+
+    if check_schema {
+        use crate::env::schema::{Schema, SchemaIssue};
+
+        let schema = Schema::find(&env_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyFileNotFoundError, _>(
+                    "No .env.schema or .env.example next to the env file",
+                )
+            })?;
+
+        let problems: Vec<String> = schema
+            .validate(&vars)
+            .iter()
+            .filter_map(|issue| match issue {
+                SchemaIssue::Missing { key, .. } => Some(format!("{} is missing", key)),
+                SchemaIssue::Malformed { key, expected, .. } => {
+                    Some(format!("{} should be {}", key, expected))
+                }
+                SchemaIssue::Unexpected { .. } => None,
+            })
+            .collect();
+
+        if !problems.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Env file does not match {}: {}",
+                schema.path.display(),
+                problems.join(", ")
+            )));
+        }
+    }
+
     let os = py.import("os")?;
     let environ = os.getattr("environ")?;
 
-    for (key, value) in parse_env_content(&plaintext) {
+    // Python keeps its own copies; ours are wiped when `vars` and `plaintext` drop.
+    for (key, value) in &vars {
         environ.set_item(key, value)?;
     }
 
@@ -1,9 +1,12 @@
 use colored::Colorize;
 use envcipher::cli;
+use envcipher::error::EnvcipherError;
 
 fn main() {
     if let Err(e) = cli::execute(std::env::args()) {
-        eprintln!("{} {}", "Error:".red().bold(), e);
-        std::process::exit(1);
+        if !matches!(e, EnvcipherError::Exit(_)) {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+        }
+        std::process::exit(e.exit_code());
     }
 }
@@ -1,4 +1,5 @@
 use envcipher::crypto::aead::{aes_decipher, aes_encipher, generate_key, generate_nonce};
+use envcipher::crypto::secret::SecretKey;
 
 #[test]
 fn round_trip_encryption() {
@@ -68,3 +69,57 @@ fn unique_nonces_generated() {
 
     assert_ne!(nonce1, nonce2);
 }
+
+#[test]
+fn secret_key_round_trips_and_hides_bytes() {
+    let key = generate_key();
+    let encoded = key.to_base64();
+
+    let decoded = SecretKey::from_base64(&encoded).unwrap();
+    assert_eq!(decoded, key);
+    assert_ne!(generate_key(), key);
+    assert_eq!(decoded.fingerprint(), key.fingerprint());
+    assert_eq!(key.fingerprint().len(), 23);
+
+    let debug = format!("{:?}", key);
+    assert!(debug.contains(&key.fingerprint()));
+    assert!(!debug.contains(&format!("{:?}", key.as_bytes())));
+
+    assert!(SecretKey::from_base64("not base64!").is_err());
+    assert!(SecretKey::from_bytes(&[0u8; 16]).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn nested_memory_guards_restore_the_original_core_limit() {
+    use envcipher::crypto::memory::MemoryGuard;
+
+    let core_limit = || {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid rlimit for getrlimit to fill in.
+        assert_eq!(unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) }, 0);
+        (limit.rlim_cur, limit.rlim_max)
+    };
+    // Start from a non-zero soft limit, so restoring 0 instead would show.
+    let (_, max) = core_limit();
+    let raised = libc::rlimit {
+        rlim_cur: max,
+        rlim_max: max,
+    };
+    // SAFETY: `raised` is a valid rlimit for setrlimit to read.
+    assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_CORE, &raised) }, 0);
+    let before = core_limit();
+
+    let outer = MemoryGuard::engage();
+    let inner = MemoryGuard::engage();
+    assert_eq!(core_limit().0, 0);
+
+    drop(inner);
+    assert_eq!(core_limit().0, 0);
+
+    drop(outer);
+    assert_eq!(core_limit(), before);
+}
@@ -1,13 +1,23 @@
 use std::{fs, path::Path};
 
 use envcipher::{
-    crypto::aead::NONCE_LEN,
+    crypto::aead::{NONCE_LEN, aes_decipher, generate_key},
+    crypto::secret::SecretString,
+    env::diff::{VarChange, diff_env},
+    env::example::render_example,
+    env::filelock::EnvFileLock,
+    env::filter::{VarFilter, glob_match},
+    env::lint::{LintIssue, LintKind, fix_env, lint_env},
+    env::merge::merge_env,
     env::parser::{
-        find_env_file, format_enciphered_text, hash_directory_path, is_enciphered,
-        parse_enciphered_file, parse_env_content,
+        EnvVars, encipher_and_verify, find_env_file, format_enciphered_text, hash_directory_path,
+        is_enciphered, parse_enciphered_file, parse_env_content, write_to_env_file,
     },
+    env::redact::Redactor,
+    env::schema::{Schema, SchemaIssue},
 };
 use tempfile::TempDir;
+use zeroize::Zeroizing;
 
 #[test]
 fn find_env_in_current_dir() {
@@ -110,3 +120,277 @@ fn parse_env_content_handles_quotes_and_comments() {
     assert_eq!(vars[2], ("SINGLE".to_string(), "foo bar".to_string()));
     assert_eq!(vars[3], ("EMPTY".to_string(), "".to_string()));
 }
+
+#[test]
+fn deciphered_secrets_are_redacted_in_debug() {
+    let vars = parse_env_content("API_KEY=sk-live-123\n");
+    assert_eq!(format!("{:?}", vars), r#"{"API_KEY": "***"}"#);
+
+    let plaintext = SecretString::from("API_KEY=sk-live-123\n");
+    assert!(!format!("{:?}", plaintext).contains("sk-live"));
+    assert_eq!(&*plaintext, "API_KEY=sk-live-123\n");
+
+    assert!(SecretString::from_utf8(Zeroizing::new(vec![0xff, 0xfe])).is_none());
+}
+
+#[test]
+fn encipher_and_verify_round_trip() {
+    let key = generate_key();
+    let plaintext = "API_KEY=abc123\n";
+
+    let enciphered = encipher_and_verify(&key, plaintext).unwrap();
+    assert!(is_enciphered(&enciphered));
+
+    let (nonce, ciphertext) = parse_enciphered_file(&enciphered).unwrap();
+    let deciphered = aes_decipher(&key, &nonce, &ciphertext).unwrap();
+    assert_eq!(deciphered.as_slice(), plaintext.as_bytes());
+}
+
+#[test]
+fn write_to_env_file_replaces_atomically() {
+    let temp = TempDir::new().unwrap();
+    let env_path = temp.path().join(".env");
+    fs::write(&env_path, "OLD=value").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&env_path, fs::Permissions::from_mode(0o640)).unwrap();
+    }
+
+    write_to_env_file(&env_path, "NEW=value").unwrap();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "NEW=value");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&env_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    // No temp files are left next to the target.
+    let entries = fs::read_dir(temp.path()).unwrap().count();
+    assert_eq!(entries, 1);
+}
+
+fn vars(content: &str) -> EnvVars {
+    parse_env_content(content)
+}
+
+#[test]
+fn diff_env_reports_added_removed_changed() {
+    let old = vars("A=1\nB=2\nC=3");
+    let new = vars("A=1\nB=20\nD=4");
+
+    let changes = diff_env(&old, &new);
+    assert_eq!(
+        changes,
+        vec![
+            VarChange::Changed {
+                key: "B".to_string(),
+                old: "2".to_string(),
+                new: "20".to_string()
+            },
+            VarChange::Removed {
+                key: "C".to_string(),
+                value: "3".to_string()
+            },
+            VarChange::Added {
+                key: "D".to_string(),
+                value: "4".to_string()
+            },
+        ]
+    );
+    assert_eq!(format!("{:?}", changes[0]), r#"Changed { key: "B", .. }"#);
+}
+
+#[test]
+fn merge_env_applies_non_conflicting_changes() {
+    let base = "# config\nA=1\nB=2\n";
+    let ours = "# config\nA=10\nB=2\nNEW=\"x y\"\n";
+    let theirs = "# config\nA=1\nB=2\nTHEIRS=3\n";
+
+    let merged = merge_env(base, ours, theirs).unwrap();
+    assert_eq!(
+        merged.as_str(),
+        "# config\nA=10\nB=2\nTHEIRS=3\nNEW=\"x y\"\n"
+    );
+}
+
+#[test]
+fn merge_env_reports_conflicts() {
+    let base = "A=1\n";
+    let ours = "A=2\n";
+    let theirs = "A=3\n";
+
+    assert_eq!(merge_env(base, ours, theirs), Err(vec!["A".to_string()]));
+}
+
+#[test]
+fn env_file_lock_is_exclusive() {
+    let temp = TempDir::new().unwrap();
+    let env_path = temp.path().join(".env");
+    fs::write(&env_path, "A=1").unwrap();
+
+    let lock = EnvFileLock::acquire(&env_path).unwrap();
+    assert!(EnvFileLock::acquire(&env_path).is_err());
+    // Kept in the project, not a shared temp dir other users can squat on.
+    assert!(temp.path().join(".envcipher/locks/.env").is_file());
+
+    drop(lock);
+    assert!(EnvFileLock::acquire(&env_path).is_ok());
+}
+
+#[test]
+fn redactor_masks_secrets_split_across_chunks() {
+    let mut redactor = Redactor::new(&vars("API_KEY=sk-live-123456\nPORT=3000\n"));
+
+    let mut output = redactor.push(b"key=sk-li");
+    // Nothing that could still become the secret is released early.
+    assert_eq!(output, b"key=");
+    output.extend(redactor.push(b"ve-123456 port=3000\n"));
+    output.extend(redactor.finish());
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "key=***API_KEY*** port=3000\n"
+    );
+}
+
+#[test]
+fn var_filter_applies_only_and_exclude_globs() {
+    assert!(glob_match("DB_*", "DB_URL"));
+    assert!(glob_match("*_KEY", "STRIPE_API_KEY"));
+    assert!(glob_match("A?I_*Y", "API_KEY"));
+    assert!(!glob_match("DB_*", "REDIS_URL"));
+
+    let filter = VarFilter::new(
+        vec!["DB_*".to_string(), "API_KEY".to_string()],
+        vec!["*_PASSWORD".to_string()],
+    );
+    let mut selected = vars("DB_URL=a\nDB_PASSWORD=b\nAPI_KEY=c\nOTHER=d\n");
+    filter.apply(&mut selected);
+
+    assert_eq!(selected, vars("DB_URL=a\nAPI_KEY=c\n"));
+}
+
+#[test]
+fn schema_reports_missing_malformed_and_unexpected() {
+    let temp = TempDir::new().unwrap();
+    let schema_path = temp.path().join(".env.schema");
+    fs::write(
+        &schema_path,
+        "# Postgres connection string\nDATABASE_URL=url\nPORT=int optional\nDEBUG=bool optional\nADMIN=email\nMODE=regex:dev|prod\n",
+    )
+    .unwrap();
+
+    let schema = Schema::load(&schema_path).unwrap();
+    let issues = schema.validate(&vars(
+        "PORT=eighty\nDEBUG=yes\nADMIN=a@b.io\nMODE=staging\nEXTRA=1\n",
+    ));
+
+    assert_eq!(
+        issues,
+        vec![
+            SchemaIssue::Missing {
+                key: "DATABASE_URL".to_string(),
+                description: Some("Postgres connection string".to_string()),
+            },
+            SchemaIssue::Malformed {
+                key: "PORT".to_string(),
+                expected: "int".to_string(),
+                description: None,
+            },
+            SchemaIssue::Malformed {
+                key: "MODE".to_string(),
+                expected: "regex:dev|prod".to_string(),
+                description: None,
+            },
+            SchemaIssue::Unexpected {
+                key: "EXTRA".to_string(),
+            },
+        ]
+    );
+
+    // Flags go before a regex, since the pattern runs to the end of the line.
+    fs::write(&schema_path, "MODE=optional regex:dev|prod\n").unwrap();
+    assert!(!Schema::load(&schema_path).unwrap().vars[0].required);
+
+    fs::write(&schema_path, "# Mode\nMODE=regex:dev|prod optional\n").unwrap();
+    let error = Schema::load(&schema_path).unwrap_err().to_string();
+    assert!(error.contains(".env.schema:2:"), "{}", error);
+    assert!(error.contains("`optional`"), "{}", error);
+}
+
+#[test]
+fn render_example_keeps_layout_and_drops_values() {
+    let plaintext = "# Database\nDB_URL=postgres://secret\n\n# API_KEY=old-secret\nAPI_KEY=\"sk-live\"\nPORT=8080\nPORT=9090\n";
+    let existing = "PORT=3000\nREMOVED=x\n";
+
+    assert_eq!(
+        render_example(plaintext, Some(existing)),
+        "# Database\nDB_URL=\n\n# API_KEY=\nAPI_KEY=\nPORT=3000\n"
+    );
+}
+
+#[test]
+fn lint_env_flags_problems_and_fixes_the_mechanical_ones() {
+    let content = "\u{feff}API_KEY=old\r\nname=x \r\n1BAD=y\r\nGREETING=hello world\r\nTOKEN=\"open\r\nEMPTY=\r\nAPI_KEY=new\r\n";
+
+    let kinds: Vec<(usize, LintKind)> = lint_env(content)
+        .into_iter()
+        .map(|issue| (issue.line, issue.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (1, LintKind::Bom),
+            (1, LintKind::CrLf),
+            (2, LintKind::TrailingWhitespace),
+            (2, LintKind::LowercaseKey("name".to_string())),
+            (3, LintKind::InvalidKey("1BAD".to_string())),
+            (4, LintKind::UnquotedSpaces("GREETING".to_string())),
+            (5, LintKind::UnbalancedQuotes("TOKEN".to_string())),
+            (6, LintKind::EmptyValue("EMPTY".to_string())),
+            (
+                7,
+                LintKind::DuplicateKey {
+                    key: "API_KEY".to_string(),
+                    first_line: 1
+                }
+            ),
+        ]
+    );
+
+    let fixed = fix_env(content);
+    assert_eq!(
+        fixed.as_str(),
+        "name=x\n1BAD=y\nGREETING=\"hello world\"\nTOKEN=\"open\nEMPTY=\nAPI_KEY=new\n"
+    );
+    assert!(lint_env(&fixed).iter().all(|issue| !issue.is_fixable()));
+    assert!(
+        lint_env("# comment\nPORT=8080\n")
+            .iter()
+            .all(|issue: &LintIssue| !issue.is_error())
+    );
+
+    // Quotes inside an unquoted value are literal, not unbalanced.
+    let inner_quotes = "GREETING=Hello \"World\"\nCMD=echo 'hi'\nRATIO=16\"\n";
+    assert_eq!(
+        lint_env(inner_quotes),
+        vec![
+            LintIssue {
+                line: 1,
+                kind: LintKind::UnquotedSpaces("GREETING".to_string())
+            },
+            LintIssue {
+                line: 2,
+                kind: LintKind::UnquotedSpaces("CMD".to_string())
+            },
+        ]
+    );
+    assert_eq!(
+        fix_env(inner_quotes).as_str(),
+        "GREETING='Hello \"World\"'\nCMD=\"echo 'hi'\"\nRATIO=16\"\n"
+    );
+}
@@ -1,6 +1,8 @@
 use std::fs;
+use std::path::Path;
 
 use assert_cmd::Command as AssertCommand;
+use predicates::prelude::PredicateBooleanExt;
 use tempfile::TempDir;
 
 // Helper to get the binary path
@@ -197,3 +199,1579 @@ fn test_key_export_import_cycle() {
     let content = fs::read_to_string(dir_b.join(".env")).unwrap();
     assert_eq!(content, "SHARED=secret");
 }
+
+#[test]
+fn test_dir_and_file_options() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    let service_dir = root.join("services").join("api");
+    fs::create_dir_all(&service_dir).unwrap();
+
+    // 1. Init a differently named file from the repo root
+    envcipher_cmd()
+        .current_dir(root)
+        .args(["-C", "services/api", "--file", "secrets.env", "init"])
+        .assert()
+        .success();
+
+    assert!(service_dir.join(".envcipher.json").exists());
+
+    let env_path = service_dir.join("secrets.env");
+    fs::write(&env_path, "API_TOKEN=abc").unwrap();
+
+    // 2. Lock it without changing directory
+    envcipher_cmd()
+        .current_dir(root)
+        .args(["lock", "-f", "services/api/secrets.env"])
+        .assert()
+        .success();
+
+    assert!(
+        fs::read_to_string(&env_path)
+            .unwrap()
+            .starts_with("ENVCIPHER:v1:")
+    );
+
+    // 3. Unlock from inside the service directory
+    envcipher_cmd()
+        .current_dir(&service_dir)
+        .args(["unlock", "--file", "secrets.env"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "API_TOKEN=abc");
+}
+
+#[test]
+fn test_file_option_missing_file() {
+    let temp = TempDir::new().unwrap();
+
+    envcipher_cmd()
+        .current_dir(temp.path())
+        .args(["lock", "--file", "missing.env"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Env file not found"));
+}
+
+#[test]
+fn test_all_projects_continue_past_failures() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    for service in ["api", "web"] {
+        let dir = root.join("services").join(service);
+        fs::create_dir_all(&dir).unwrap();
+
+        envcipher_cmd()
+            .current_dir(&dir)
+            .arg("init")
+            .assert()
+            .success();
+
+        fs::write(dir.join(".env"), format!("SERVICE={}", service)).unwrap();
+    }
+
+    // A marker without a stored key must fail on its own without aborting the rest.
+    let broken = root.join("services").join("broken");
+    fs::create_dir_all(&broken).unwrap();
+    fs::write(broken.join(".envcipher.json"), "{}").unwrap();
+    fs::write(broken.join(".env"), "BROKEN=1").unwrap();
+
+    envcipher_cmd()
+        .current_dir(root)
+        .args(["lock", "--all"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("services/api: locked"))
+        .stdout(predicates::str::contains("services/web: locked"))
+        .stderr(predicates::str::contains("1 of 3 projects failed"));
+
+    envcipher_cmd()
+        .current_dir(root)
+        .env("NO_COLOR", "1")
+        .args(["status", "--recursive"])
+        .assert()
+        .stdout(predicates::str::is_match(r"services/api\s+Locked").unwrap())
+        .stdout(predicates::str::is_match(r"services/broken\s+Unlocked").unwrap());
+
+    envcipher_cmd()
+        .current_dir(root)
+        .args(["--file", ".env", "status", "--recursive"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "--file cannot be combined with --recursive",
+        ));
+
+    fs::remove_dir_all(&broken).unwrap();
+
+    envcipher_cmd()
+        .current_dir(root)
+        .args(["unlock", "--all"])
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(root.join("services/web/.env")).unwrap();
+    assert_eq!(content, "SERVICE=web");
+}
+
+#[test]
+fn test_status_json_and_exit_codes() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    fs::write(current_dir.join(".env"), "TOKEN=abc").unwrap();
+
+    // Plaintext on disk exits with 2.
+    let output = envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["status", "--json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stderr.is_empty());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["initialized"], true);
+    assert_eq!(report["state"], "unlocked");
+    assert_eq!(report["key_present"], true);
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    let output = envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["status", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["state"], "locked");
+    assert_eq!(report["format_version"], "v1");
+    assert_eq!(report["key_id"].as_str().unwrap().len(), 8);
+    assert!(report["last_modified"].as_str().unwrap().ends_with('Z'));
+}
+
+#[test]
+fn test_doctor_reports_problems() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    fs::write(current_dir.join(".env"), "TOKEN=abc").unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("envcipher init"));
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("decrypts with the stored key"));
+}
+
+#[test]
+fn test_doctor_asks_git_for_hook_and_attributes() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(current_dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+    };
+
+    git(&["init", "-q"]);
+    git(&["config", "core.hooksPath", "githooks"]);
+    fs::create_dir(current_dir.join("githooks")).unwrap();
+    fs::write(
+        current_dir.join("githooks/pre-commit"),
+        "#!/bin/sh\nenvcipher status >/dev/null\n",
+    )
+    .unwrap();
+    fs::write(current_dir.join(".gitattributes"), ".e* filter=envcipher\n").unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("pre-commit hook runs envcipher"))
+        .stdout(predicates::str::contains("git has no such filter"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fallback_editor_is_hardened() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    use envcipher::cli::edit::hardening_args;
+
+    let temp = TempDir::new().unwrap();
+    let bin = temp.path();
+    for dir in ["debian", "fedora", "busybox"] {
+        fs::create_dir(bin.join(dir)).unwrap();
+    }
+    let script = |name: &str, body: &str| {
+        let path = bin.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let link = |target: &str, name: &str| {
+        symlink(target, bin.join(name)).unwrap();
+        bin.join(name).to_str().unwrap().to_string()
+    };
+
+    let vim_flags = hardening_args("vim");
+    assert!(vim_flags.contains(&"-n"));
+    for editor in ["vim.basic", "vim.tiny", "/usr/bin/nvim"] {
+        assert_eq!(hardening_args(editor), vim_flags, "{}", editor);
+    }
+
+    // `vi` gets vim's flags only when it really is vim.
+    let alternative = link(&script("vim.basic", "exit 0"), "alternatives-vi");
+    assert_eq!(hardening_args(&link(&alternative, "debian/vi")), vim_flags);
+    assert_eq!(
+        hardening_args(&script("fedora/vi", "echo 'VIM - Vi IMproved 9.1'")),
+        vim_flags
+    );
+
+    let busybox = script("busybox/busybox", "exit 1");
+    assert_eq!(
+        hardening_args(&link(&busybox, "busybox/vi")),
+        Vec::<&str>::new()
+    );
+    let nvi = script("view", "echo 'vi: illegal option -- -' >&2; exit 1");
+    assert_eq!(hardening_args(&nvi), Vec::<&str>::new());
+
+    assert_eq!(hardening_args("nano"), vec!["--restricted"]);
+    assert_eq!(hardening_args("code"), Vec::<&str>::new());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_merges_concurrent_change() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    fs::write(current_dir.join(".env"), "A=1\n").unwrap();
+
+    // The "editor" saves A=2 while something else rewrites .env underneath it.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env(
+            "EDITOR",
+            r#"sh -c 'printf "A=2\n" > "$0"; printf "A=1\nB=1\n" > .env'"#,
+        )
+        .arg("edit")
+        .write_stdin("m\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("changed on disk"));
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(current_dir.join(".env")).unwrap();
+    assert_eq!(content, "A=2\nB=1\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_history_and_restore() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    fs::write(current_dir.join(".env"), "A=1\nB=2\n").unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    // A bad edit drops B.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("EDITOR", r#"sh -c 'printf "A=1\n" > "$0"'"#)
+        .arg("edit")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("NO_COLOR", "1")
+        .arg("history")
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"1\s+\S+\s+2\s+-B").unwrap());
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "1"])
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(current_dir.join(".env")).unwrap();
+    assert_eq!(content, "A=1\nB=2\n");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "99"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No backup #99"));
+
+    // A file enciphered with someone else's key can still be rolled back, and is kept.
+    let foreign = TempDir::new().unwrap();
+    envcipher_cmd()
+        .current_dir(foreign.path())
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(foreign.path().join(".env"), "FOREIGN=1\n").unwrap();
+    envcipher_cmd()
+        .current_dir(foreign.path())
+        .arg("lock")
+        .assert()
+        .success();
+    fs::copy(foreign.path().join(".env"), current_dir.join(".env")).unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "1"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("saved as backup #1"));
+
+    // Rolling back onto identical contents does not add a duplicate backup.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "2"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("saved as backup #1"));
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "3"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("saved as backup").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_diff_against_head_and_file() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(current_dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+    };
+
+    git(&["init", "-q"]);
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    fs::write(current_dir.join(".env"), "A=1\nB=2\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    git(&["add", ".env"]);
+    git(&["commit", "-q", "-m", "secrets"]);
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("EDITOR", r#"sh -c 'printf "A=10\nC=3\n" > "$0"'"#)
+        .arg("edit")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("NO_COLOR", "1")
+        .arg("diff")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("~ A: **** -> ****"))
+        .stdout(predicates::str::contains("- B=****"))
+        .stdout(predicates::str::contains("+ C=****"));
+
+    fs::write(current_dir.join("plain.env"), "A=10\nC=4\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("NO_COLOR", "1")
+        .args(["diff", "plain.env", "--show-values"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("~ C: 4 -> 3"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unlock_makes_plaintext_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+    let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    assert_eq!(mode(&env_path), 0o600);
+
+    fs::write(&env_path, "SECRET=value\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+    fs::set_permissions(&env_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+    assert_eq!(mode(&env_path), 0o600);
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["lock", "--keep-mode"])
+        .assert()
+        .success();
+    assert_eq!(mode(&env_path), 0o644);
+
+    // Plaintext readable by others is called out.
+    fs::write(&env_path, "SECRET=value\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("status")
+        .assert()
+        .code(2)
+        .stdout(predicates::str::contains("readable by group/others"));
+}
+
+#[test]
+fn test_unlock_for_relocks_after_duration() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(&env_path, "SECRET=value\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["unlock", "--for", "1s"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Re-locking in 1s"));
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "SECRET=value\n");
+
+    fs::write(&env_path, "SECRET=changed\n").unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    while !fs::read_to_string(&env_path)
+        .unwrap()
+        .starts_with("ENVCIPHER:v1:")
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "file was not re-locked"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+
+    // The watcher logs after writing; give it a moment to finish.
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let log = fs::read_to_string(current_dir.join(".envcipher/autolock.log")).unwrap();
+    assert!(log.contains("contents changed since unlock"));
+    assert!(log.contains("locked (timer expired)"));
+
+    // A second unlock is refused, and a manual lock stands the watcher down.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["unlock", "--for", "1m"])
+        .assert()
+        .success();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["unlock", "--for", "1m"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("not enciphered"));
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    while !fs::read_to_string(current_dir.join(".envcipher/autolock.log"))
+        .unwrap()
+        .contains("watcher exiting")
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "watcher did not stand down"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    assert!(!current_dir.join(".envcipher/autolock/.env").exists());
+}
+
+#[test]
+fn test_unlock_for_waits_for_a_busy_file() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(&env_path, "A=1\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["unlock", "--for", "1s"])
+        .assert()
+        .success();
+
+    // Another envcipher process holds the file while the timer expires.
+    let held = fs::File::open(current_dir.join(".envcipher/locks/.env")).unwrap();
+    held.lock().unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(4));
+
+    let log = fs::read_to_string(current_dir.join(".envcipher/autolock.log")).unwrap();
+    assert!(log.contains("busy in another envcipher process; retrying"));
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "A=1\n");
+    assert!(current_dir.join(".envcipher/autolock/.env").exists());
+
+    drop(held);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    while !fs::read_to_string(&env_path)
+        .unwrap()
+        .starts_with("ENVCIPHER:v1:")
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "file was not re-locked"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    assert!(!current_dir.join(".envcipher/autolock/.env").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unlock_for_holds_the_key_and_reports_failures() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let temp = TempDir::new().unwrap();
+    let socket = temp.path().join("agent").join("agent.sock");
+    let project = temp.path().join("project");
+    let env_path = project.join(".env");
+    fs::create_dir(&project).unwrap();
+    fs::write(project.join(".envcipher.json"), "{}").unwrap();
+    fs::write(&env_path, "SECRET=value\n").unwrap();
+
+    let agent_cmd = || {
+        let mut cmd = envcipher_cmd();
+        cmd.current_dir(&project)
+            .env("ENVCIPHER_AGENT_SOCK", &socket);
+        cmd
+    };
+    let wait_for = |what: &str, done: &dyn Fn() -> bool| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+        while !done() {
+            assert!(std::time::Instant::now() < deadline, "{}", what);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+    };
+    let is_locked = || {
+        fs::read_to_string(&env_path).is_ok_and(|contents| contents.starts_with("ENVCIPHER:v1:"))
+    };
+
+    // The key exists only in the agent, which is gone by the time the timer expires.
+    agent_cmd()
+        .args(["agent", "start", "--ttl", "1m"])
+        .assert()
+        .success();
+    let dir_hash = envcipher::env::parser::hash_directory_path(&project.canonicalize().unwrap());
+    let mut stream = UnixStream::connect(&socket).unwrap();
+    writeln!(stream, "PUT {} {}=", dir_hash, "A".repeat(43)).unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    stream.read_to_string(&mut String::new()).unwrap();
+
+    agent_cmd().arg("lock").assert().success();
+    agent_cmd()
+        .args(["unlock", "--for", "2s"])
+        .assert()
+        .success();
+    let log_path = project.join(".envcipher/autolock.log");
+    wait_for("watcher did not start", &|| {
+        fs::read_to_string(&log_path).is_ok_and(|log| log.contains("re-locking in"))
+    });
+    agent_cmd().args(["agent", "stop"]).assert().success();
+
+    wait_for("file was not re-locked", &is_locked);
+
+    // A watcher that cannot lock leaves a marker for status and doctor.
+    agent_cmd()
+        .args(["agent", "start", "--ttl", "1m"])
+        .assert()
+        .success();
+    let mut stream = UnixStream::connect(&socket).unwrap();
+    writeln!(stream, "PUT {} {}=", dir_hash, "A".repeat(43)).unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    stream.read_to_string(&mut String::new()).unwrap();
+
+    agent_cmd()
+        .args(["unlock", "--for", "1s"])
+        .assert()
+        .success();
+    fs::remove_file(&env_path).unwrap();
+    fs::create_dir(&env_path).unwrap();
+    wait_for("failure was not recorded", &|| {
+        project.join(".envcipher/autolock-failed/.env").exists()
+    });
+    fs::remove_dir(&env_path).unwrap();
+    fs::write(&env_path, "SECRET=value\n").unwrap();
+
+    let output = agent_cmd().args(["status", "--json"]).output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report["autolock_failed"].is_string(), "{}", report);
+    agent_cmd()
+        .arg("doctor")
+        .assert()
+        .stdout(predicates::str::contains("could not re-lock"));
+
+    agent_cmd().arg("lock").assert().success();
+    let output = agent_cmd().args(["status", "--json"]).output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report.get("autolock_failed").is_none(), "{}", report);
+
+    agent_cmd().args(["agent", "stop"]).assert().success();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_agent_caches_keys() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let socket = temp.path().join("agent").join("agent.sock");
+    let agent_cmd = || {
+        let mut cmd = envcipher_cmd();
+        cmd.current_dir(current_dir)
+            .env("ENVCIPHER_AGENT_SOCK", &socket);
+        cmd
+    };
+
+    agent_cmd()
+        .args(["agent", "status"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Not running"));
+
+    agent_cmd()
+        .args(["agent", "start", "--ttl", "1m"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Agent started!"));
+
+    // A client that connects and never sends a request does not hold up others.
+    let silent = std::os::unix::net::UnixStream::connect(&socket).unwrap();
+    let started = std::time::Instant::now();
+    agent_cmd()
+        .args(["agent", "status"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("0 cached"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    drop(silent);
+
+    agent_cmd().arg("init").assert().success();
+    fs::write(current_dir.join(".env"), "SECRET=value\n").unwrap();
+    agent_cmd().arg("lock").assert().success();
+
+    agent_cmd()
+        .args(["agent", "status"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 cached"));
+
+    agent_cmd()
+        .args(["run", "--", "sh", "-c", "test \"$SECRET\" = value"])
+        .assert()
+        .success();
+
+    agent_cmd()
+        .args(["agent", "stop"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Agent stopped"));
+
+    // Without an agent, commands go straight to the keychain.
+    agent_cmd().arg("unlock").assert().success();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_status_counts_keys_held_by_the_agent() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let temp = TempDir::new().unwrap();
+    let socket = temp.path().join("agent").join("agent.sock");
+    let project = temp.path().join("project");
+    fs::create_dir(&project).unwrap();
+    fs::write(project.join(".envcipher.json"), "{}").unwrap();
+    fs::write(project.join(".env"), "A=1\n").unwrap();
+
+    let agent_cmd = || {
+        let mut cmd = envcipher_cmd();
+        cmd.current_dir(&project)
+            .env("ENVCIPHER_AGENT_SOCK", &socket);
+        cmd
+    };
+    agent_cmd()
+        .args(["agent", "start", "--ttl", "1m"])
+        .assert()
+        .success();
+
+    // The key exists only in the agent, never in the keychain.
+    let dir_hash = envcipher::env::parser::hash_directory_path(&project.canonicalize().unwrap());
+    let mut stream = UnixStream::connect(&socket).unwrap();
+    writeln!(stream, "PUT {} {}=", dir_hash, "A".repeat(43)).unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    assert_eq!(reply, "OK\n");
+
+    let output = agent_cmd().args(["status", "--json"]).output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["key_present"], true);
+
+    agent_cmd()
+        .arg("doctor")
+        .assert()
+        .stdout(predicates::str::contains("cached by the agent"))
+        // The keychain is still checked, and does not have the key.
+        .stdout(predicates::str::contains("no key stored for"));
+
+    agent_cmd().args(["agent", "stop"]).assert().success();
+}
+
+/// An `envcipher` command limited to at most 8 MiB of locked memory, the common
+/// default. As root it also loses CAP_IPC_LOCK, like in a default container.
+#[cfg(target_os = "linux")]
+fn small_memlock_cmd() -> AssertCommand {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_envcipher"));
+    // SAFETY: only async-signal-safe syscalls on locals run between fork and exec.
+    unsafe {
+        command.pre_exec(|| {
+            #[repr(C)]
+            struct CapHeader {
+                version: u32,
+                pid: i32,
+            }
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            struct CapData {
+                effective: u32,
+                permitted: u32,
+                inheritable: u32,
+            }
+            const CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+            const CAP_IPC_LOCK: u32 = 14;
+
+            let mut header = CapHeader {
+                version: CAPABILITY_VERSION_3,
+                pid: 0,
+            };
+            let mut data = [CapData {
+                effective: 0,
+                permitted: 0,
+                inheritable: 0,
+            }; 2];
+            if libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) == 0 {
+                data[0].effective &= !(1 << CAP_IPC_LOCK);
+                libc::syscall(libc::SYS_capset, &mut header, data.as_ptr());
+            }
+
+            let mut limit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit);
+            limit.rlim_cur = limit.rlim_max.min(8 * 1024 * 1024);
+            limit.rlim_max = limit.rlim_cur;
+            if libc::setrlimit(libc::RLIMIT_MEMLOCK, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    AssertCommand::from_std(command)
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_commands_work_under_a_small_memlock_limit() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+
+    // Far more plaintext than the limit lets the process lock.
+    let mut content = String::new();
+    for index in 0..200_000 {
+        content.push_str(&format!("FILLER_{}={:040}\n", index, index));
+    }
+    fs::write(current_dir.join(".env"), &content).unwrap();
+
+    for command in ["lock", "unlock"] {
+        small_memlock_cmd()
+            .current_dir(current_dir)
+            .arg(command)
+            .assert()
+            .success();
+    }
+    assert_eq!(
+        fs::read_to_string(current_dir.join(".env")).unwrap(),
+        content
+    );
+
+    // Redaction reads the child's output on extra threads.
+    fs::write(current_dir.join(".env"), "TEST_SECRET=supersecure\n").unwrap();
+    small_memlock_cmd()
+        .current_dir(current_dir)
+        .args(["run", "--redact", "--", "sh", "-c", "echo $TEST_SECRET"])
+        .assert()
+        .success()
+        .stdout("***TEST_SECRET***\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_redact_masks_output_and_keeps_exit_code() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "TEST_SECRET=supersecure\n").unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["run", "--redact", "--", "sh", "-c"])
+        .arg("echo out=$TEST_SECRET; echo err=$TEST_SECRET >&2; exit 7")
+        .assert()
+        .code(7)
+        .stdout("out=***TEST_SECRET***\n")
+        .stderr("err=***TEST_SECRET***\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_environment_flags() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(
+        current_dir.join(".env"),
+        "DB_URL=postgres://db\nDB_PASSWORD=hunter2\nAPI_KEY=from-file\n",
+    )
+    .unwrap();
+
+    let printenv = "echo \"$DB_URL|$DB_PASSWORD|$API_KEY|$INHERITED\"";
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("API_KEY", "from-shell")
+        .env("INHERITED", "yes")
+        .args(["run", "--", "sh", "-c", printenv])
+        .assert()
+        .success()
+        .stdout("postgres://db|hunter2|from-file|yes\n");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("API_KEY", "from-shell")
+        .args(["run", "--no-override", "--", "sh", "-c", printenv])
+        .assert()
+        .success()
+        .stdout("postgres://db|hunter2|from-shell|\n");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("INHERITED", "yes")
+        .args([
+            "run",
+            "--clean",
+            "--only",
+            "DB_*",
+            "--exclude",
+            "*_PASSWORD",
+        ])
+        .args(["--", "sh", "-c", printenv])
+        .assert()
+        .success()
+        .stdout("postgres://db|||\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_execs_and_forwards_signals() {
+    use std::process::{Command, Stdio};
+
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "TEST_SECRET=supersecure\n").unwrap();
+
+    // Without a parent to keep around, the command replaces envcipher.
+    let child = Command::new(env!("CARGO_BIN_EXE_envcipher"))
+        .current_dir(current_dir)
+        .args(["run", "--", "sh", "-c", "echo $$"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        pid.to_string()
+    );
+
+    // Signal deaths are reported as 128 + signal.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["run", "--redact", "--", "sh", "-c", "kill -TERM $$"])
+        .assert()
+        .code(128 + 15);
+
+    // With --redact envcipher stays in between and relays SIGTERM to the command.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_envcipher"))
+        .current_dir(current_dir)
+        .args(["run", "--redact", "--", "sleep", "30"])
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(128 + 15));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shell_loads_vars_and_marks_session() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let project = current_dir
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "TEST_SECRET=supersecure\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("SHELL", "sh")
+        .arg("shell")
+        .write_stdin("echo \"$TEST_SECRET in $ENVCIPHER_ACTIVE\"; exit 3\n")
+        .assert()
+        .code(3)
+        .stdout(predicates::str::contains(format!(
+            "supersecure in {}",
+            project
+        )))
+        .stdout(predicates::str::contains("Left envcipher shell"));
+
+    // Nothing was written to disk.
+    let content = fs::read_to_string(current_dir.join(".env")).unwrap();
+    assert!(content.starts_with("ENVCIPHER:v1:"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_watch_restarts_on_change() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(&env_path, "VALUE=one\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_envcipher"))
+        .current_dir(current_dir)
+        .args([
+            "run",
+            "--watch",
+            "--",
+            "sh",
+            "-c",
+            "echo \"$VALUE\"; exec sleep 30",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (lines_tx, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let _ = lines_tx.send(line.unwrap());
+        }
+    });
+
+    let timeout = Duration::from_secs(10);
+    assert_eq!(lines.recv_timeout(timeout).unwrap(), "one");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+    fs::write(&env_path, "VALUE=two\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    assert_eq!(lines.recv_timeout(timeout).unwrap(), "two");
+
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(128 + 15));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_check_validates_against_schema() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "PORT=eighty\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("check")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No .env.schema or .env.example"));
+
+    // .env.example works as a schema of required keys.
+    fs::write(
+        current_dir.join(".env.example"),
+        "PORT=\n# Where to send mail\nSMTP_HOST=\n",
+    )
+    .unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("check")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("SMTP_HOST"))
+        .stdout(predicates::str::contains("Where to send mail"))
+        .stderr(predicates::str::contains("1 check(s) failed"));
+
+    // An explicit schema takes precedence and adds types.
+    fs::write(current_dir.join(".env.schema"), "PORT=int\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("check")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("expected int"))
+        .stdout(predicates::str::contains("eighty").not());
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["run", "--check", "--", "echo", "started"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("started").not());
+
+    fs::write(current_dir.join(".env.schema"), "PORT=string\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["run", "--check", "--", "echo", "started"])
+        .assert()
+        .success()
+        .stdout("started\n");
+
+    // A relative --schema is resolved like --file, against the -C directory.
+    let elsewhere = TempDir::new().unwrap();
+    envcipher_cmd()
+        .current_dir(elsewhere.path())
+        .arg("-C")
+        .arg(current_dir)
+        .args(["check", "--schema", ".env.schema"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("All variables match the schema."));
+}
+
+#[test]
+fn test_example_writes_and_checks() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let example_path = current_dir.join(".env.example");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "# Stripe\nSTRIPE_KEY=sk-live\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["example", "--check"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("out of date"));
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("example")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&example_path).unwrap(),
+        "# Stripe\nSTRIPE_KEY=\n"
+    );
+
+    // Meant to be committed, so it gets the umask's permissions rather than 0600.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let reference = current_dir.join("reference");
+        fs::write(&reference, "").unwrap();
+        assert_eq!(mode(&example_path), mode(&reference));
+    }
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["example", "--check"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_lint_reports_and_fixes_locked_file() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(
+        &env_path,
+        "API_KEY=old\nGREETING=hello world  \nAPI_KEY=s3cr3t-new\n",
+    )
+    .unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lint")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("line 3"))
+        .stdout(predicates::str::contains("duplicate key `API_KEY`"))
+        .stdout(predicates::str::contains("s3cr3t").not());
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["lint", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No problems found."));
+    assert!(
+        fs::read_to_string(&env_path)
+            .unwrap()
+            .starts_with("ENVCIPHER:v1:")
+    );
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&env_path).unwrap(),
+        "GREETING=\"hello world\"\nAPI_KEY=s3cr3t-new\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_validates_before_saving() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(&env_path, "A=1\n").unwrap();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("lock")
+        .assert()
+        .success();
+
+    // The first save is broken; re-editing fixes it.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env(
+            "EDITOR",
+            r#"sh -c 'if grep -q oops "$0"; then printf "A=2\n" > "$0"; else printf "A=1\noops\n" > "$0"; fi'"#,
+        )
+        .arg("edit")
+        .write_stdin("e\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("line 2"))
+        .stdout(predicates::str::contains("expected KEY=VALUE"))
+        .stdout(predicates::str::contains("Changes saved"));
+
+    // Invalid UTF-8 can only be re-edited or discarded; closed stdin discards.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("EDITOR", r#"sh -c 'printf "A=\377\n" > "$0"'"#)
+        .arg("edit")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("not valid UTF-8 (line 1)"))
+        .stdout(predicates::str::contains("Discarded"));
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env("EDITOR", r#"sh -c 'printf "A=\"open\n" > "$0"'"#)
+        .arg("edit")
+        .write_stdin("s\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("unbalanced quotes"));
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("unlock")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "A=\"open\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_uses_private_scratch_file() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "A=1\n").unwrap();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .env(
+            "EDITOR",
+            r#"sh -c 'echo "$0" > scratch; stat -c %a "$(dirname "$0")" > mode; printf "A=2\n" > "$0"'"#,
+        )
+        .arg("edit")
+        .assert()
+        .success();
+
+    let scratch = fs::read_to_string(current_dir.join("scratch")).unwrap();
+    let scratch = Path::new(scratch.trim());
+    assert_eq!(scratch.file_name().unwrap(), ".env");
+    assert!(!scratch.exists());
+    assert!(!scratch.parent().unwrap().exists());
+    assert_eq!(
+        fs::read_to_string(current_dir.join("mode")).unwrap().trim(),
+        "700"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_run_disables_core_dumps_only_for_envcipher() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .arg("init")
+        .assert()
+        .success();
+    fs::write(current_dir.join(".env"), "A=1\n").unwrap();
+
+    // envcipher stays around as the parent with --redact; the command reports both its
+    // parent's core limit and its own.
+    let output = std::process::Command::new("sh")
+        .current_dir(current_dir)
+        .arg("-c")
+        .arg(
+            r#"ulimit -S -c 2048; exec "$0" run --redact -- sh -c 'grep "Max core" /proc/$PPID/limits; ulimit -S -c'"#,
+        )
+        .arg(env!("CARGO_BIN_EXE_envcipher"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let parent_soft_limit = lines
+        .next()
+        .unwrap()
+        .split_whitespace()
+        .nth(4)
+        .unwrap()
+        .to_string();
+    assert_eq!(parent_soft_limit, "0");
+    assert_eq!(lines.next(), Some("2048"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unlock_lock_cycles_keep_history_meaningful() {
+    let temp = TempDir::new().unwrap();
+    let current_dir = temp.path();
+    let env_path = current_dir.join(".env");
+
+    let envcipher = |arg: &str| {
+        envcipher_cmd()
+            .current_dir(current_dir)
+            .arg(arg)
+            .assert()
+            .success();
+    };
+
+    envcipher("init");
+    fs::write(&env_path, "A=1\n").unwrap();
+    envcipher("lock");
+
+    // Round trips that change nothing add no backups.
+    for _ in 0..3 {
+        envcipher("unlock");
+        envcipher("lock");
+    }
+
+    envcipher("unlock");
+    fs::write(&env_path, "A=2\n").unwrap();
+    envcipher("lock");
+
+    let output = envcipher_cmd()
+        .current_dir(current_dir)
+        .env("NO_COLOR", "1")
+        .arg("history")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+        .collect();
+
+    // Only the version the last edit replaced is kept, not copies of what is current.
+    assert_eq!(rows.len(), 1, "{}", stdout);
+    assert!(rows[0].contains("~A"), "{}", stdout);
+
+    // Restoring over a timed unlock stands its watcher down, like `lock` does.
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["unlock", "--for", "1m"])
+        .assert()
+        .success();
+    envcipher_cmd()
+        .current_dir(current_dir)
+        .args(["restore", "1"])
+        .assert()
+        .success();
+    assert!(!current_dir.join(".envcipher/autolock/.env").exists());
+
+    envcipher("unlock");
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "A=1\n");
+}
@@ -1,5 +1,6 @@
 use envcipher::{
     crypto::aead::generate_key,
+    error::EnvcipherError,
     keystore::retrieve_key_from_store as retrieve_key,
     keystore::{delete_key, key_exists, store_key},
 };
@@ -28,6 +29,7 @@ fn retrieve_nonexistent_key_fails() {
 
     let result = retrieve_key(&hash);
     assert!(result.is_err());
+    assert!(matches!(result, Err(EnvcipherError::KeyNotFound(_))));
 }
 
 #[test]